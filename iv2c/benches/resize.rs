@@ -0,0 +1,103 @@
+//! Times the per-frame hot path (`ImagePipeline::resize` and `cells_to_ascii`) on a synthetic
+//! 4K frame and counts the allocations it makes. RGB frames are resized from their own
+//! buffer, other formats are converted once, which shows up as one frame sized allocation.
+//!
+//! Run with `cargo bench -p iv2c --bench resize`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use image::{DynamicImage, Rgb, RgbImage};
+use iv2c::{
+    maps::CharMap,
+    pipeline::{ImagePipeline, Resolution},
+};
+
+/// The system allocator, counting the allocations and allocated bytes.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const WIDTH: u32 = 3840;
+const HEIGHT: u32 = 2160;
+const ITERATIONS: u32 = 20;
+
+fn main() {
+    // a diagonal gradient, so every cell has a different color and char
+    let rgb = RgbImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        Rgb([
+            (x * 255 / WIDTH) as u8,
+            (y * 255 / HEIGHT) as u8,
+            ((x + y) % 256) as u8,
+        ])
+    });
+    let frames = [
+        ("rgb8", DynamicImage::ImageRgb8(rgb.clone())),
+        (
+            "rgba8",
+            DynamicImage::ImageRgba8(DynamicImage::ImageRgb8(rgb).to_rgba8()),
+        ),
+    ];
+    let pipeline = ImagePipeline::new(Resolution::Fixed(160, 45), CharMap::Chars1, true)
+        .expect("valid pipeline");
+
+    println!("{WIDTH}x{HEIGHT} -> 160x45, {ITERATIONS} iterations");
+    for (name, frame) in &frames {
+        // warm up, e.g. the lazily set up resizer state
+        convert(&pipeline, frame);
+
+        let (allocations, bytes) = (
+            ALLOCATIONS.load(Ordering::Relaxed),
+            ALLOCATED_BYTES.load(Ordering::Relaxed),
+        );
+        let started = Instant::now();
+        for _ in 0..ITERATIONS {
+            black_box(convert(&pipeline, black_box(frame)));
+        }
+        let elapsed = started.elapsed();
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+        let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes;
+
+        println!(
+            "{name:>6}: {:>8.2} ms/frame, {:>4} allocations/frame, {:>10.1} KiB/frame",
+            (elapsed / ITERATIONS).as_secs_f64() * 1000.0,
+            allocations / ITERATIONS as usize,
+            bytes as f64 / ITERATIONS as f64 / 1024.0,
+        );
+    }
+}
+
+/// Resizes `frame` and converts it to chars, like every rendered frame is.
+fn convert(pipeline: &ImagePipeline, frame: &DynamicImage) -> String {
+    let resized = pipeline.resize(frame).expect("resizable frame");
+    let cells = resized.to_luma8();
+    pipeline
+        .cells_to_ascii(frame, &cells)
+        .expect("convertible frame")
+}
@@ -0,0 +1,362 @@
+use std::{fmt::Write as _, path::Path};
+
+use crate::error::Error;
+
+/// How colors are encoded in ANSI output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorDepth {
+    /// 24-bit `38;2;r;g;b` sequences.
+    #[default]
+    TrueColor,
+    /// The xterm 256-color palette (`38;5;n`), for terminals without truecolor support.
+    Ansi256,
+    /// The 16 standard colors (`30-37`/`90-97`), for terminals and logs that support nothing
+    /// more.
+    Ansi16,
+    /// No color sequences, plain text.
+    None,
+}
+
+impl ColorDepth {
+    /// Appends the SGR sequence selecting `rgb` as the foreground, or the background if
+    /// `background` is set.
+    pub(crate) fn push_sgr(self, out: &mut String, [r, g, b]: [u8; 3], background: bool) {
+        let target = if background { 48 } else { 38 };
+        match self {
+            ColorDepth::TrueColor => {
+                let _ = write!(out, "\x1b[{target};2;{r};{g};{b}m");
+            }
+            ColorDepth::Ansi256 => {
+                let _ = write!(out, "\x1b[{target};5;{}m", rgb_to_ansi256([r, g, b]));
+            }
+            ColorDepth::Ansi16 => {
+                let index = rgb_to_ansi16([r, g, b]);
+                // normal colors start at 30/40, bright ones at 90/100
+                let code = match (index, background) {
+                    (0..8, false) => 30 + index,
+                    (0..8, true) => 40 + index,
+                    (_, false) => 90 + index - 8,
+                    (_, true) => 100 + index - 8,
+                };
+                let _ = write!(out, "\x1b[{code}m");
+            }
+            ColorDepth::None => {}
+        }
+    }
+
+    /// Appends the SGR sequence restoring the default foreground (or background).
+    pub(crate) fn push_reset(self, out: &mut String, background: bool) {
+        if self != ColorDepth::None {
+            out.push_str(if background { "\x1b[49m" } else { "\x1b[39m" });
+        }
+    }
+}
+
+/// A fixed set of colors the output is snapped to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    colors: Vec<[u8; 3]>,
+}
+
+/// Names of the built-in palettes, see `Palette::named`.
+pub const PALETTE_NAMES: &[&str] = &["cga", "gameboy", "pico8", "gray4"];
+
+impl Palette {
+    /// A palette of the given colors, fails if there are none.
+    pub fn new(colors: Vec<[u8; 3]>) -> Result<Self, Error> {
+        if colors.is_empty() {
+            return Err(Error::Application(
+                "A palette needs at least one color".to_string(),
+            ));
+        }
+        Ok(Self { colors })
+    }
+
+    /// One of the built-in palettes in `PALETTE_NAMES`.
+    pub fn named(name: &str) -> Option<Self> {
+        let colors: &[u32] = match name.to_lowercase().as_str() {
+            "cga" => &[
+                0x000000, 0x0000aa, 0x00aa00, 0x00aaaa, 0xaa0000, 0xaa00aa, 0xaa5500, 0xaaaaaa,
+                0x555555, 0x5555ff, 0x55ff55, 0x55ffff, 0xff5555, 0xff55ff, 0xffff55, 0xffffff,
+            ],
+            "gameboy" => &[0x0f380f, 0x306230, 0x8bac0f, 0x9bbc0f],
+            "pico8" => &[
+                0x000000, 0x1d2b53, 0x7e2553, 0x008751, 0xab5236, 0x5f574f, 0xc2c3c7, 0xfff1e8,
+                0xff004d, 0xffa300, 0xffec27, 0x00e436, 0x29adff, 0x83769c, 0xff77a8, 0xffccaa,
+            ],
+            "gray4" => &[0x000000, 0x555555, 0xaaaaaa, 0xffffff],
+            _ => return None,
+        };
+        let colors = colors
+            .iter()
+            .map(|rgb| [(rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8])
+            .collect();
+        Some(Self { colors })
+    }
+
+    /// Parses a built-in palette name or a comma separated list of `#rrggbb` colors.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        if let Some(palette) = Self::named(s.trim()) {
+            return Ok(palette);
+        }
+        let colors = s
+            .split(',')
+            .map(|color| {
+                parse_hex(color).ok_or_else(|| {
+                    Error::Application(format!(
+                        "Invalid palette color `{color}`, expected #rrggbb or one of: {}",
+                        PALETTE_NAMES.join(", ")
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Self::new(colors)
+    }
+
+    /// Reads a palette file with one `rrggbb` color per line (the `.hex` format of palette
+    /// sites like Lospec), lines starting with `;` or `//` are comments.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let colors = std::fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with(';') && !line.starts_with("//"))
+            .map(|line| {
+                parse_hex(line).ok_or_else(|| {
+                    Error::Application(format!(
+                        "Invalid color `{line}` in palette {}",
+                        path.display()
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Self::new(colors)
+    }
+
+    pub fn colors(&self) -> &[[u8; 3]] {
+        &self.colors
+    }
+
+    /// The palette color closest to `rgb`.
+    pub fn nearest(&self, rgb: [u8; 3]) -> [u8; 3] {
+        self.colors
+            .iter()
+            .copied()
+            .min_by_key(|&color| distance(rgb, color))
+            .unwrap_or(rgb)
+    }
+
+    /// Floyd-Steinberg dithers `colors`, rows of `width` colors, against the palette.
+    pub fn dither(&self, colors: &mut [[u8; 3]], width: usize) {
+        dither(colors, width, |rgb| self.nearest(rgb));
+    }
+}
+
+/// A scientific colormap luminance is mapped through instead of using the source colors,
+/// the dark end for black and the bright end for white.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colormap {
+    Viridis,
+    Magma,
+    Inferno,
+    Plasma,
+}
+
+impl Colormap {
+    /// The colormap sampled at nine evenly spaced points, as matplotlib defines it.
+    fn stops(self) -> &'static [[u8; 3]; 9] {
+        match self {
+            Colormap::Viridis => &[
+                [68, 1, 84],
+                [71, 45, 123],
+                [59, 82, 139],
+                [44, 114, 142],
+                [33, 145, 140],
+                [40, 174, 128],
+                [94, 201, 98],
+                [173, 220, 48],
+                [253, 231, 37],
+            ],
+            Colormap::Magma => &[
+                [0, 0, 4],
+                [28, 16, 68],
+                [79, 18, 123],
+                [129, 37, 129],
+                [181, 54, 122],
+                [229, 80, 100],
+                [251, 135, 97],
+                [254, 194, 135],
+                [252, 253, 191],
+            ],
+            Colormap::Inferno => &[
+                [0, 0, 4],
+                [31, 12, 72],
+                [85, 15, 109],
+                [136, 34, 106],
+                [186, 54, 85],
+                [227, 89, 51],
+                [249, 142, 9],
+                [249, 203, 53],
+                [252, 255, 164],
+            ],
+            Colormap::Plasma => &[
+                [13, 8, 135],
+                [76, 2, 161],
+                [126, 3, 168],
+                [169, 35, 149],
+                [204, 71, 120],
+                [229, 107, 93],
+                [248, 149, 64],
+                [253, 197, 39],
+                [240, 249, 33],
+            ],
+        }
+    }
+
+    /// Lookup table from luminance to color, interpolating linearly between the stops.
+    pub fn lut(self) -> [[u8; 3]; 256] {
+        let stops = self.stops();
+        let segments = (stops.len() - 1) as f32;
+        std::array::from_fn(|lum| {
+            let position = lum as f32 / 255.0 * segments;
+            let i = (position as usize).min(stops.len() - 2);
+            let t = position - i as f32;
+            std::array::from_fn(|c| {
+                let (from, to) = (stops[i][c] as f32, stops[i + 1][c] as f32);
+                (from + (to - from) * t).round() as u8
+            })
+        })
+    }
+}
+
+/// Parses a color given as `#rrggbb` (the `#` is optional).
+pub fn parse_hex(s: &str) -> Option<[u8; 3]> {
+    let hex = s.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Channel levels of the 6x6x6 color cube in the xterm 256-color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// The closest entry of the xterm 256-color palette, either from the color cube (16-231) or
+/// the gray ramp (232-255).
+pub fn rgb_to_ansi256(rgb: [u8; 3]) -> u8 {
+    let cube_index = |c: u8| {
+        CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, level)| (c as i32 - **level as i32).abs())
+            .map_or(0, |(i, _)| i)
+    };
+    let [r, g, b] = rgb.map(cube_index);
+    let cube = [CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]];
+
+    // gray ramp runs from 8 to 238 in steps of 10
+    let mean = rgb.iter().map(|&c| c as u32).sum::<u32>() / 3;
+    let gray_index = (mean.saturating_sub(3) / 10).min(23) as u8;
+    let gray_level = 8 + 10 * gray_index;
+
+    if distance(rgb, [gray_level; 3]) < distance(rgb, cube) {
+        232 + gray_index
+    } else {
+        16 + 36 * r as u8 + 6 * g as u8 + b as u8
+    }
+}
+
+/// The standard colors 0-15 as xterm shows them by default.
+const ANSI16: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [205, 0, 0],
+    [0, 205, 0],
+    [205, 205, 0],
+    [0, 0, 238],
+    [205, 0, 205],
+    [0, 205, 205],
+    [229, 229, 229],
+    [127, 127, 127],
+    [255, 0, 0],
+    [0, 255, 0],
+    [255, 255, 0],
+    [92, 92, 255],
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 255],
+];
+
+/// The closest (euclidean distance) of the 16 standard colors, 0-7 are the normal colors and
+/// 8-15 their bright variants.
+pub fn rgb_to_ansi16(rgb: [u8; 3]) -> u8 {
+    (0..16)
+        .min_by_key(|&i| distance(rgb, ANSI16[i as usize]))
+        .unwrap_or(0)
+}
+
+/// The color of an entry of the xterm 256-color palette.
+pub fn ansi256_to_rgb(index: u8) -> [u8; 3] {
+    match index {
+        0..16 => ANSI16[index as usize],
+        16..232 => {
+            let i = (index - 16) as usize;
+            [
+                CUBE_LEVELS[i / 36],
+                CUBE_LEVELS[i / 6 % 6],
+                CUBE_LEVELS[i % 6],
+            ]
+        }
+        _ => [8 + 10 * (index - 232); 3],
+    }
+}
+
+/// Floyd-Steinberg dithers `colors`, rows of `width` colors, against the xterm 256-color
+/// palette, replacing every color with the palette color it is quantized to. Smooth gradients
+/// turn into a mix of neighbouring palette colors instead of flat bands.
+pub fn dither_ansi256(colors: &mut [[u8; 3]], width: usize) {
+    dither(colors, width, |rgb| ansi256_to_rgb(rgb_to_ansi256(rgb)));
+}
+
+/// Floyd-Steinberg dithers `colors`, rows of `width` colors, against the 16 standard colors,
+/// see `dither_ansi256`.
+pub fn dither_ansi16(colors: &mut [[u8; 3]], width: usize) {
+    dither(colors, width, |rgb| ANSI16[rgb_to_ansi16(rgb) as usize]);
+}
+
+/// Floyd-Steinberg dithers `colors`, rows of `width` colors, replacing every color with the
+/// one `quantize` maps it to and spreading the difference over the neighbours.
+fn dither(colors: &mut [[u8; 3]], width: usize, quantize: impl Fn([u8; 3]) -> [u8; 3]) {
+    if width == 0 {
+        return;
+    }
+
+    // quantization error carried to the pixels right and below, indexed like `colors`
+    let mut errors = vec![[0_f32; 3]; colors.len() + width + 1];
+    for i in 0..colors.len() {
+        let x = i % width;
+        let wanted: [f32; 3] = std::array::from_fn(|c| colors[i][c] as f32 + errors[i][c]);
+        let quantized = quantize(wanted.map(|c| c.clamp(0.0, 255.0) as u8));
+        colors[i] = quantized;
+
+        for c in 0..3 {
+            let error = wanted[c] - quantized[c] as f32;
+            if x + 1 < width {
+                errors[i + 1][c] += error * 7.0 / 16.0;
+                errors[i + width + 1][c] += error / 16.0;
+            }
+            if x > 0 {
+                errors[i + width - 1][c] += error * 3.0 / 16.0;
+            }
+            errors[i + width][c] += error * 5.0 / 16.0;
+        }
+    }
+}
+
+/// Squared euclidean distance between two colors.
+pub(crate) fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(b)
+        .map(|(&a, b)| (a as i32 - b as i32).pow(2) as u32)
+        .sum()
+}
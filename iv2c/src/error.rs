@@ -9,23 +9,56 @@ pub enum Error {
     Application(String),
     #[error("Image pipeline error: {0}")]
     Pipeline(String),
+    /// An I/O operation failed, the original `io::Error` is kept as the source.
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    /// OpenCV reported an error, the original `opencv::Error` is kept as the source.
+    #[error("OpenCV error: {0}")]
+    Opencv(#[from] opencv::Error),
+    /// The media could neither be opened by extension nor as a video.
+    #[error(
+        "Unsupported format `{0}`, supported formats are: {formats}",
+        formats = crate::frames::SUPPORTED_FORMATS.join(", ")
+    )]
+    UnsupportedFormat(String),
+    /// A required external tool (e.g. `ffprobe`) could not be spawned.
+    #[error("Missing dependency `{tool}`: {hint}")]
+    MissingDependency { tool: String, hint: String },
+    /// A media decoder failed, `context` describes what was being decoded.
+    #[error("{context}: {source}")]
+    Decode {
+        context: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
 }
 
-impl From<Error> for io::Error {
-    fn from(error: Error) -> Self {
-        io::Error::other(error.to_string())
+impl Error {
+    /// Creates an `Error::Decode` from a context message and the underlying decoder error.
+    pub fn decode(
+        context: &str,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Self {
+        Error::Decode {
+            context: context.to_string(),
+            source: source.into(),
+        }
     }
 }
 
-impl From<io::Error> for Error {
-    fn from(error: io::Error) -> Self {
-        Error::Application(format!("{error}"))
+/// Decoding errors of the `image` crate, kept as the source of an `Error::Decode`.
+impl From<image::ImageError> for Error {
+    fn from(error: image::ImageError) -> Self {
+        Error::decode(ERROR_DECODING_IMAGE, error)
     }
 }
 
-impl From<opencv::Error> for Error {
-    fn from(error: opencv::Error) -> Self {
-        Error::Application(format!("{error}"))
+impl From<Error> for io::Error {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Io(error) => error,
+            error => io::Error::other(error),
+        }
     }
 }
 
@@ -41,3 +74,7 @@ pub const ERROR_READING_GIF_HEADER: &str = "Cannot read GIF header";
 pub const ERROR_DATA: &str = "Data error";
 /// Error message for issues related to resizing an image.
 pub const ERROR_RESIZE: &str = "Image resizing error";
+/// Error message for char maps without any visible character.
+pub const ERROR_EMPTY_CHAR_MAP: &str = "Char map must contain at least one visible character";
+/// Error message for animations whose decoded frames exceed the memory budget.
+pub const ERROR_DECODE_BUDGET: &str = "Decoded frames exceed the memory budget";
@@ -1,27 +1,131 @@
 use crate::{
     error::*,
-    util::{extract_fps, mat_to_dynamic_image},
+    util::{extract_fps, has_video_stream, mat_to_dynamic_image, sample_fps},
 };
 use gif;
 use image::{DynamicImage, ImageReader};
+#[cfg(feature = "avif")]
+use libavif_sys as avif;
 use libwebp_sys as webp;
 use opencv::{prelude::*, videoio::VideoCapture};
-use std::{fs::File, io::Read, path::Path};
+use std::{fs::File, io::Read, path::Path, time::Duration};
 
 #[derive(Debug)]
 pub enum FrameIterator {
     Image(Option<DynamicImage>),
-    Video(VideoCapture),
+    Video {
+        capture: VideoCapture,
+        /// Consecutive failed reads retried before a stream of unknown length counts as ended.
+        read_retries: u32,
+    },
     AnimatedImage {
         frames: Vec<DynamicImage>,
+        /// How long each frame is shown, aligned with `frames`.
+        delays: Vec<Duration>,
+        current_frame: usize,
+    },
+    /// A still image shown as `count` identical frames, timed by the playback fps. See
+    /// `MediaData::repeat_still`.
+    RepeatedImage {
+        image: DynamicImage,
+        count: usize,
         current_frame: usize,
     },
 }
 
+/// Where `MediaData::fps` comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FpsSource {
+    /// The media has no frame rate (single images).
+    None,
+    /// The stream's frame rate as reported by ffprobe.
+    Ffprobe,
+    /// OpenCV's frame rate, used when ffprobe reports none.
+    OpenCv,
+    /// The average of the per-frame delays of animated images.
+    FrameDelays,
+    /// Measured from the timestamps of the first frames, see `OpenOptions::detect_fps`.
+    Sampled,
+}
+
 #[derive(Debug)]
 pub struct MediaData {
     pub frame_iter: FrameIterator,
     pub fps: Option<f64>,
+    pub fps_source: FpsSource,
+}
+
+impl MediaData {
+    /// Turns a still image into `count` identical frames, so it can be exported as an animation
+    /// (e.g. `count = seconds * fps`). Returns `false` and leaves other media untouched.
+    pub fn repeat_still(&mut self, count: usize) -> bool {
+        let FrameIterator::Image(image) = &mut self.frame_iter else {
+            return false;
+        };
+        let Some(image) = image.take() else {
+            return false;
+        };
+        self.frame_iter = FrameIterator::RepeatedImage {
+            image,
+            count,
+            current_frame: 0,
+        };
+        true
+    }
+
+    /// The per-frame delays of animated images (GIF, WebP), `None` for other media.
+    pub fn frame_delays(&self) -> Option<&[Duration]> {
+        match &self.frame_iter {
+            FrameIterator::AnimatedImage { delays, .. } => Some(delays),
+            _ => None,
+        }
+    }
+
+    /// Total number of frames, `None` if the video container doesn't report it.
+    pub fn frame_count(&self) -> Option<u64> {
+        match &self.frame_iter {
+            FrameIterator::Image(_) => Some(1),
+            FrameIterator::Video { capture: video, .. } => video
+                .get(opencv::videoio::CAP_PROP_FRAME_COUNT)
+                .ok()
+                .filter(|count| *count > 0.0)
+                .map(|count| count as u64),
+            FrameIterator::AnimatedImage { frames, .. } => Some(frames.len() as u64),
+            FrameIterator::RepeatedImage { count, .. } => Some(*count as u64),
+        }
+    }
+
+    /// Size of the frames in pixels, without decoding one. `None` if the video container
+    /// doesn't report it or there are no frames left.
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        match &self.frame_iter {
+            FrameIterator::Image(img) => img.as_ref().map(|img| (img.width(), img.height())),
+            FrameIterator::Video { capture: video, .. } => {
+                let get = |prop| video.get(prop).ok().filter(|value| *value > 0.0);
+                Some((
+                    get(opencv::videoio::CAP_PROP_FRAME_WIDTH)? as u32,
+                    get(opencv::videoio::CAP_PROP_FRAME_HEIGHT)? as u32,
+                ))
+            }
+            FrameIterator::AnimatedImage { frames, .. } => {
+                frames.first().map(|frame| (frame.width(), frame.height()))
+            }
+            FrameIterator::RepeatedImage { image, .. } => Some((image.width(), image.height())),
+        }
+    }
+
+    /// Playback duration, from the frame delays of animated images or the frame count and fps
+    /// of videos and repeated images. `None` for single images and when either is unknown.
+    pub fn duration(&self) -> Option<Duration> {
+        match &self.frame_iter {
+            FrameIterator::Image(_) => None,
+            FrameIterator::AnimatedImage { delays, .. } => Some(delays.iter().sum()),
+            FrameIterator::Video { .. } | FrameIterator::RepeatedImage { .. } => {
+                let (count, fps) = (self.frame_count()?, self.fps?);
+                Duration::try_from_secs_f64(count as f64 / fps).ok()
+            }
+        }
+    }
 }
 
 impl Iterator for FrameIterator {
@@ -30,10 +134,14 @@ impl Iterator for FrameIterator {
     fn next(&mut self) -> Option<Self::Item> {
         match self {
             FrameIterator::Image(img) => img.take(),
-            FrameIterator::Video(video) => capture_video_frame(video),
+            FrameIterator::Video {
+                capture,
+                read_retries,
+            } => capture_video_frame(capture, *read_retries),
             FrameIterator::AnimatedImage {
                 frames,
                 current_frame,
+                ..
             } => {
                 if *current_frame == frames.len() {
                     None
@@ -43,17 +151,44 @@ impl Iterator for FrameIterator {
                     frame
                 }
             }
+            FrameIterator::RepeatedImage {
+                image,
+                count,
+                current_frame,
+            } => {
+                if *current_frame == *count {
+                    None
+                } else {
+                    *current_frame += 1;
+                    Some(image.clone())
+                }
+            }
         }
     }
 }
 
 impl FrameIterator {
+    /// Index of the frame `next` returns next, without decoding anything. `None` if the video
+    /// backend doesn't report it.
+    pub fn position(&self) -> Option<u64> {
+        match self {
+            FrameIterator::Image(img) => Some(u64::from(img.is_none())),
+            FrameIterator::Video { capture: video, .. } => video
+                .get(opencv::videoio::CAP_PROP_POS_FRAMES)
+                .ok()
+                .filter(|position| *position >= 0.0)
+                .map(|position| position as u64),
+            FrameIterator::AnimatedImage { current_frame, .. }
+            | FrameIterator::RepeatedImage { current_frame, .. } => Some(*current_frame as u64),
+        }
+    }
+
     pub fn skip_frames(&mut self, n: usize) {
         match self {
             FrameIterator::Image(_) => {
                 // For a single image, skipping is a no-op, since there's only one frame
             }
-            FrameIterator::Video(video) => {
+            FrameIterator::Video { capture: video, .. } => {
                 for _ in 0..n {
                     let mut frame = Mat::default();
                     if !video.read(&mut frame).unwrap_or(false) || frame.empty() {
@@ -64,9 +199,111 @@ impl FrameIterator {
             FrameIterator::AnimatedImage {
                 current_frame,
                 frames,
+                ..
+            } => {
+                // stop at the end instead of wrapping, so the animation can finish
+                *current_frame = (*current_frame + n).min(frames.len());
+            }
+            FrameIterator::RepeatedImage {
+                count,
+                current_frame,
+                ..
+            } => {
+                *current_frame = (*current_frame + n).min(*count);
+            }
+        }
+    }
+
+    /// Skips the frames within the next `duration` of playback, going by the delays of animated
+    /// images and `fps` otherwise (and for frames without a delay). Stops at the end.
+    pub fn skip_duration(&mut self, duration: Duration, fps: f64) {
+        let frame_secs = if fps > 0.0 { 1.0 / fps } else { 0.0 };
+        match self {
+            FrameIterator::AnimatedImage {
+                frames,
+                delays,
+                current_frame,
             } => {
-                *current_frame = (*current_frame + n) % frames.len();
+                let mut remaining = duration.as_secs_f64();
+                while *current_frame < frames.len() {
+                    let delay = delays
+                        .get(*current_frame)
+                        .filter(|delay| !delay.is_zero())
+                        .map_or(frame_secs, Duration::as_secs_f64);
+                    if delay > remaining || delay <= 0.0 {
+                        break;
+                    }
+                    remaining -= delay;
+                    *current_frame += 1;
+                }
             }
+            _ if frame_secs > 0.0 => {
+                self.skip_frames((duration.as_secs_f64() / frame_secs) as usize);
+            }
+            _ => {}
+        }
+    }
+
+    /// Seeks to `position`. Animated images locate the frame by their delays, `fps` is used for
+    /// those without any.
+    pub fn seek(&mut self, position: Duration, fps: f64) {
+        match self {
+            FrameIterator::Image(_) => {
+                // For a single image, seeking is a no-op, since there's only one frame
+            }
+            FrameIterator::Video { capture: video, .. } => {
+                let _ = video.set(
+                    opencv::videoio::CAP_PROP_POS_MSEC,
+                    position.as_secs_f64() * 1000.0,
+                );
+            }
+            FrameIterator::AnimatedImage {
+                frames,
+                delays,
+                current_frame,
+            } => {
+                let frame = if delays.iter().any(|delay| !delay.is_zero()) {
+                    // the frame shown at `position`, once the frames before it have ended
+                    let mut elapsed = Duration::ZERO;
+                    delays
+                        .iter()
+                        .take_while(|delay| {
+                            elapsed += **delay;
+                            elapsed <= position
+                        })
+                        .count()
+                } else {
+                    (position.as_secs_f64() * fps.max(0_f64)) as usize
+                };
+                *current_frame = frame.min(frames.len());
+            }
+            FrameIterator::RepeatedImage {
+                count,
+                current_frame,
+                ..
+            } => {
+                let frame = (position.as_secs_f64() * fps.max(0_f64)) as usize;
+                *current_frame = frame.min(*count);
+            }
+        }
+    }
+
+    /// How long the frame last returned by `next` should be shown, `None` when the media has
+    /// no per-frame timing (or the frame has a zero delay, which players treat as unknown).
+    pub fn current_frame_duration(&self) -> Option<Duration> {
+        match self {
+            FrameIterator::AnimatedImage {
+                delays,
+                current_frame,
+                ..
+            } => current_frame
+                .checked_sub(1)
+                .and_then(|frame| delays.get(frame))
+                .copied()
+                .filter(|delay| !delay.is_zero()),
+            FrameIterator::Image(_)
+            | FrameIterator::Video { .. }
+            | FrameIterator::RepeatedImage { .. } => None,
         }
     }
 
@@ -75,108 +312,512 @@ impl FrameIterator {
             FrameIterator::Image(_) => {
                 // For a single image, reset is a no-op, since there's only one frame
             }
-            FrameIterator::Video(video) => {
+            FrameIterator::Video { capture: video, .. } => {
                 let _ = video.set(opencv::videoio::CAP_PROP_POS_AVI_RATIO, 0.0);
             }
-            FrameIterator::AnimatedImage { current_frame, .. } => {
+            FrameIterator::AnimatedImage { current_frame, .. }
+            | FrameIterator::RepeatedImage { current_frame, .. } => {
                 *current_frame = 0;
             }
         }
     }
 }
 
+/// File extensions `open_media` knows how to decode.
+#[cfg(not(feature = "avif"))]
+pub const SUPPORTED_FORMATS: &[&str] = &[
+    "png", "bmp", "ico", "tif", "tiff", "jpg", "jpeg", "mp4", "avi", "webm", "mkv", "mov", "flv",
+    "ogg", "gif", "webp",
+];
+/// File extensions `open_media` knows how to decode.
+#[cfg(feature = "avif")]
+pub const SUPPORTED_FORMATS: &[&str] = &[
+    "png", "bmp", "ico", "tif", "tiff", "jpg", "jpeg", "mp4", "avi", "webm", "mkv", "mov", "flv",
+    "ogg", "gif", "webp", "avif",
+];
+
+/// Options for opening media.
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions {
+    /// Index of the video stream to decode, for files with several video tracks.
+    pub video_stream: usize,
+    /// Upper bound for the decoded frames of animated images (GIF, WebP, AVIF) in bytes, which are
+    /// decoded up front. `None` for no limit.
+    pub max_decode_bytes: Option<usize>,
+    /// Keep the frames decoded so far when `max_decode_bytes` is exceeded instead of failing.
+    pub truncate_over_budget: bool,
+    /// How many consecutive failed or empty reads of a video are retried before it counts as
+    /// ended, for network streams that hiccup mid-stream. Retries wait longer each time (see
+    /// `RETRY_BACKOFF`) to give the stream time to recover. Reads at the end of files with a
+    /// known frame count are never retried.
+    pub video_read_retries: u32,
+    /// Measure the frame rate of videos from the timestamps of their first frames and use it
+    /// when it differs noticeably from the reported one, for variable frame rate content. Costs
+    /// a pass over those frames when opening.
+    pub detect_fps: bool,
+}
+
+impl OpenOptions {
+    /// Whether another `decoded` bytes of frames fit the budget. Fails when they don't, unless
+    /// `truncate_over_budget` is set, in which case decoding should stop.
+    fn fits_budget(&self, decoded: usize) -> Result<bool, Error> {
+        let Some(budget) = self.max_decode_bytes else {
+            return Ok(true);
+        };
+        if decoded <= budget {
+            return Ok(true);
+        }
+        if self.truncate_over_budget {
+            tracing::warn!(budget, "{ERROR_DECODE_BUDGET}, truncating the animation");
+            Ok(false)
+        } else {
+            Err(Error::Application(format!(
+                "{ERROR_DECODE_BUDGET} of {budget} bytes"
+            )))
+        }
+    }
+}
+
 pub fn open_media_from_path(path: &Path) -> Result<MediaData, Error> {
-    let fps = extract_fps(path);
+    open_media(path, &OpenOptions::default())
+}
 
+#[tracing::instrument(skip(options), fields(stream = options.video_stream))]
+pub fn open_media(path: &Path, options: &OpenOptions) -> Result<MediaData, Error> {
     let ext = path.extension().and_then(std::ffi::OsStr::to_str);
+    let media = if path.is_dir() {
+        tracing::debug!("opening directory as image sequence");
+        MediaData {
+            frame_iter: open_image_dir(path, options)?,
+            fps: None,
+            fps_source: FpsSource::None,
+        }
+    } else {
+        tracing::debug!(?ext, "detecting format from extension");
+        open_media_by_extension(path, ext, options)?
+    };
+    tracing::info!(fps = ?media.fps, fps_source = ?media.fps_source, "opened media");
+    Ok(media)
+}
+
+fn open_media_by_extension(
+    path: &Path,
+    ext: Option<&str>,
+    options: &OpenOptions,
+) -> Result<MediaData, Error> {
     match ext {
         // Image extensions
-        Some("png") | Some("bmp") | Some("ico") | Some("tif") | Some("tiff") | Some("jpg")
-        | Some("jpeg") => Ok(MediaData {
-            frame_iter: open_image(path)?,
+        Some("png") | Some("bmp") | Some("tif") | Some("tiff") | Some("jpg") | Some("jpeg") => {
+            Ok(MediaData {
+                frame_iter: open_image(path)?,
+                fps: None,
+                fps_source: FpsSource::None,
+            })
+        }
+        // Icons
+        Some("ico") => Ok(MediaData {
+            frame_iter: open_ico(path)?,
             fps: None,
+            fps_source: FpsSource::None,
         }),
         // Video extensions
         Some("mp4") | Some("avi") | Some("webm") | Some("mkv") | Some("mov") | Some("flv")
-        | Some("ogg") => Ok(MediaData {
-            frame_iter: open_video(path)?,
-            fps,
-        }),
+        | Some("ogg") => open_video_media(path, options),
         // Gif
         Some("gif") => {
-            let (frame_iter, fps) = open_gif(path)?;
+            let (frame_iter, fps) = open_gif(path, options)?;
             Ok(MediaData {
                 frame_iter,
                 fps: Some(fps),
+                fps_source: FpsSource::FrameDelays,
             })
         }
         // Webp
         Some("webp") => {
-            let (frame_iter, fps) = open_webp(path)?;
+            let (frame_iter, fps) = open_webp(path, options)?;
             Ok(MediaData {
                 frame_iter,
                 fps: Some(fps),
+                fps_source: FpsSource::FrameDelays,
             })
         }
+        // Avif, stills have no frame rate
+        #[cfg(feature = "avif")]
+        Some("avif") => match open_avif(path, options)? {
+            (FrameIterator::AnimatedImage { frames, .. }, _) if frames.len() == 1 => {
+                Ok(MediaData {
+                    frame_iter: FrameIterator::Image(frames.into_iter().next()),
+                    fps: None,
+                    fps_source: FpsSource::None,
+                })
+            }
+            (frame_iter, fps) => Ok(MediaData {
+                frame_iter,
+                fps: Some(fps),
+                fps_source: FpsSource::FrameDelays,
+            }),
+        },
         // Unknown extension, try open as video
-        _ => Ok(MediaData {
-            frame_iter: open_video(path)?,
-            fps,
-        }),
+        _ => open_unknown_media(path, ext, options),
+    }
+}
+
+/// Opens media with an unknown extension as a video. Fails with `Error::UnsupportedFormat`
+/// when ffprobe finds no video stream in it, or OpenCV cannot open it or read a first frame.
+fn open_unknown_media(
+    path: &Path,
+    ext: Option<&str>,
+    options: &OpenOptions,
+) -> Result<MediaData, Error> {
+    let unsupported = || Error::UnsupportedFormat(ext.unwrap_or_default().to_string());
+    match has_video_stream(path) {
+        Ok(true) => {}
+        Ok(false) => {
+            tracing::debug!("ffprobe found no video stream in the unknown extension");
+            return Err(unsupported());
+        }
+        // OpenCV still tells whether it is a video
+        Err(err) => tracing::debug!(%err, "cannot probe the unknown extension for video"),
+    }
+    let mut media = open_video_media(path, options).map_err(|err| match err {
+        Error::Opencv(_) | Error::Application(_) => {
+            tracing::debug!(%err, "unknown extension did not open as video");
+            unsupported()
+        }
+        err => err,
+    })?;
+    if let FrameIterator::Video { capture, .. } = &mut media.frame_iter {
+        // some backends open anything, only a frame proves it is a video
+        if !capture.grab().unwrap_or(false) {
+            tracing::debug!("unknown extension opened as video without frames");
+            return Err(unsupported());
+        }
+        let _ = capture.set(opencv::videoio::CAP_PROP_POS_FRAMES, 0.0);
+    }
+    Ok(media)
+}
+
+fn open_video_media(path: &Path, options: &OpenOptions) -> Result<MediaData, Error> {
+    // query ffprobe first, it reports missing streams more clearly than opencv
+    let fps = match extract_fps(path, options.video_stream) {
+        // without ffprobe the frame rate comes from OpenCV, only another stream than the first
+        // cannot be checked
+        Err(err @ Error::MissingDependency { .. }) if options.video_stream == 0 => {
+            tracing::warn!(%err, "cannot query the frame rate, falling back to OpenCV");
+            None
+        }
+        fps => fps?,
+    };
+    let mut frame_iter = open_video(path, options)?;
+    let (fps, fps_source) = match (fps, &frame_iter) {
+        (Some(fps), _) => (Some(fps), FpsSource::Ffprobe),
+        (None, FrameIterator::Video { capture: video, .. }) => {
+            match video.get(opencv::videoio::CAP_PROP_FPS) {
+                Ok(fps) if fps > 0.0 => {
+                    tracing::info!(fps, "ffprobe reported no frame rate, using OpenCV's");
+                    (Some(fps), FpsSource::OpenCv)
+                }
+                _ => (None, FpsSource::None),
+            }
+        }
+        (None, _) => (None, FpsSource::None),
+    };
+    let (fps, fps_source) = match (options.detect_fps, &mut frame_iter) {
+        (true, FrameIterator::Video { capture, .. }) => {
+            detect_video_fps(capture, fps).unwrap_or((fps, fps_source))
+        }
+        _ => (fps, fps_source),
+    };
+    if fps.is_none() {
+        tracing::warn!("no frame rate detected, falling back to the default");
     }
+    Ok(MediaData {
+        frame_iter,
+        fps,
+        fps_source,
+    })
 }
 
-fn capture_video_frame(video: &mut VideoCapture) -> Option<DynamicImage> {
+/// Frames sampled by `OpenOptions::detect_fps`.
+const FPS_SAMPLE_FRAMES: usize = 120;
+
+/// Relative difference from the reported frame rate above which the sampled one is used.
+const FPS_DIVERGENCE: f64 = 0.05;
+
+/// The sampled frame rate, if it diverges from `reported`.
+fn detect_video_fps(
+    video: &mut VideoCapture,
+    reported: Option<f64>,
+) -> Option<(Option<f64>, FpsSource)> {
+    let sampled = sample_fps(video, FPS_SAMPLE_FRAMES)?;
+    match reported {
+        Some(reported) if ((sampled - reported) / reported).abs() <= FPS_DIVERGENCE => {
+            tracing::debug!(reported, sampled, "sampled fps matches the reported one");
+            None
+        }
+        _ => {
+            tracing::info!(?reported, sampled, "using the sampled fps");
+            Some((Some(sampled), FpsSource::Sampled))
+        }
+    }
+}
+
+/// Wait before the first retry of a failed video read, doubled for every further retry up to
+/// `MAX_RETRY_BACKOFF`.
+const RETRY_BACKOFF: Duration = Duration::from_millis(5);
+/// Longest wait between retries of failed video reads.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// How long to wait before retry `attempt` (starting at 1) of a failed video read.
+fn retry_backoff(attempt: u32) -> Duration {
+    RETRY_BACKOFF
+        .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+        .min(MAX_RETRY_BACKOFF)
+}
+
+/// Reads the next frame, retrying up to `retries` consecutive failed or empty reads with a
+/// growing backoff unless the video is at its last frame.
+fn capture_video_frame(video: &mut VideoCapture, retries: u32) -> Option<DynamicImage> {
     let mut frame = Mat::default();
-    if video.read(&mut frame).unwrap_or(false) && !frame.empty() {
-        mat_to_dynamic_image(&frame)
-    } else {
-        None
+    let mut attempt = 0;
+    loop {
+        match video.read(&mut frame) {
+            Ok(true) if !frame.empty() => {
+                let image = mat_to_dynamic_image(&frame);
+                if image.is_none() {
+                    tracing::warn!(
+                        depth = frame.depth(),
+                        channels = frame.channels(),
+                        "unsupported video frame layout, stopping"
+                    );
+                }
+                return image;
+            }
+            _ if at_video_end(video) => {
+                tracing::debug!("no more video frames");
+                return None;
+            }
+            Ok(_) if attempt < retries => {
+                attempt += 1;
+                tracing::info!(attempt, retries, "empty video read, retrying");
+                std::thread::sleep(retry_backoff(attempt));
+            }
+            Err(err) if attempt < retries => {
+                attempt += 1;
+                tracing::info!(%err, attempt, retries, "failed to read video frame, retrying");
+                std::thread::sleep(retry_backoff(attempt));
+            }
+            Ok(_) => {
+                tracing::debug!(attempts = attempt + 1, "no more video frames");
+                return None;
+            }
+            Err(err) => {
+                tracing::warn!(%err, "failed to decode video frame, stopping");
+                return None;
+            }
+        }
+    }
+}
+
+/// Whether the read position has reached the frame count, `false` for streams that don't
+/// report one.
+fn at_video_end(video: &VideoCapture) -> bool {
+    let get = |prop| video.get(prop).ok().filter(|value| *value > 0.0);
+    match (
+        get(opencv::videoio::CAP_PROP_POS_FRAMES),
+        get(opencv::videoio::CAP_PROP_FRAME_COUNT),
+    ) {
+        (Some(position), Some(count)) => position >= count,
+        _ => false,
     }
 }
 
 fn open_image(path: &Path) -> Result<FrameIterator, Error> {
-    let img = ImageReader::open(path)?
-        .decode()
-        .map_err(|e| Error::Application(format!("{ERROR_DECODING_IMAGE}: {e:?}")))?;
+    let img = ImageReader::open(path)?.decode()?;
     Ok(FrameIterator::Image(Some(img)))
 }
 
-fn open_video(path: &Path) -> Result<FrameIterator, Error> {
-    let video = VideoCapture::from_file(
-        path.to_str().expect(ERROR_OPENING_VIDEO),
-        opencv::videoio::CAP_ANY,
-    )?;
+/// Opens the images of directory `path` in natural order (`frame_2` before `frame_10`) as an
+/// animation. The frames have no delays of their own, so they play at the given fps.
+fn open_image_dir(path: &Path, open_options: &OpenOptions) -> Result<FrameIterator, Error> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let entry_path = entry?.path();
+        let is_image = entry_path
+            .extension()
+            .and_then(std::ffi::OsStr::to_str)
+            .is_some_and(|ext| {
+                matches!(
+                    ext.to_lowercase().as_str(),
+                    "png" | "bmp" | "ico" | "tif" | "tiff" | "jpg" | "jpeg"
+                )
+            });
+        if is_image {
+            paths.push(entry_path);
+        } else if entry_path.is_file() {
+            tracing::warn!(path = %entry_path.display(), "skipping non-image file");
+        }
+    }
+    paths.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+
+    let mut frames = Vec::with_capacity(paths.len());
+    let mut decoded_bytes = 0;
+    for frame_path in paths {
+        let frame = ImageReader::open(&frame_path)?.decode()?;
+        decoded_bytes += frame.as_bytes().len();
+        if !open_options.fits_budget(decoded_bytes)? {
+            break;
+        }
+        frames.push(frame);
+    }
+    if frames.is_empty() {
+        return Err(Error::Application(format!(
+            "{ERROR_OPENING_RESOURCE}: no images in {}",
+            path.display()
+        )));
+    }
+
+    Ok(FrameIterator::AnimatedImage {
+        delays: vec![Duration::ZERO; frames.len()],
+        frames,
+        current_frame: 0,
+    })
+}
+
+/// Compares strings with runs of digits ordered by their numeric value.
+fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return std::cmp::Ordering::Equal,
+            (None, Some(_)) => return std::cmp::Ordering::Less,
+            (Some(_), None) => return std::cmp::Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(c);
+                    }
+                    digits
+                };
+                let (x, y) = (number(&mut a), number(&mut b));
+                let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let ordering = x.len().cmp(&y.len()).then_with(|| x.cmp(y));
+                if ordering.is_ne() {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// Opens the largest image of an icon. The `image` decoder prefers color depth over size,
+/// which picks tiny 16x16 layers of favicons that also ship 256x256 ones.
+fn open_ico(path: &Path) -> Result<FrameIterator, Error> {
+    let data = std::fs::read(path)?;
+    let Some(icon) = largest_ico_entry(&data) else {
+        return open_image(path);
+    };
+    let img = image::load_from_memory_with_format(&icon, image::ImageFormat::Ico)?;
+    Ok(FrameIterator::Image(Some(img)))
+}
+
+/// Builds an icon holding only the largest entry of the icon `data`, `None` if the directory
+/// can't be read.
+fn largest_ico_entry(data: &[u8]) -> Option<Vec<u8>> {
+    const HEADER_LEN: usize = 6;
+    const ENTRY_LEN: usize = 16;
+    let u16_at = |at: usize| Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?));
+    let u32_at = |at: usize| Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?));
+
+    let count = u16_at(4)? as usize;
+    let entry = (0..count)
+        .map(|i| HEADER_LEN + i * ENTRY_LEN)
+        .filter(|&at| at + ENTRY_LEN <= data.len())
+        // a stored size of 0 means 256
+        .max_by_key(|&at| {
+            let size = |b: u8| if b == 0 { 256 } else { b as u32 };
+            size(data[at]) * size(data[at + 1])
+        })?;
+
+    let size = u32_at(entry + 8)? as usize;
+    let offset = u32_at(entry + 12)? as usize;
+    let image = data.get(offset..offset.checked_add(size)?)?;
+
+    let mut icon = Vec::with_capacity(HEADER_LEN + ENTRY_LEN + image.len());
+    // reserved, type (1 = icon), one entry
+    icon.extend_from_slice(&[0, 0, 1, 0, 1, 0]);
+    icon.extend_from_slice(&data[entry..entry + 12]);
+    icon.extend_from_slice(&((HEADER_LEN + ENTRY_LEN) as u32).to_le_bytes());
+    icon.extend_from_slice(image);
+    Some(icon)
+}
+
+fn open_video(path: &Path, options: &OpenOptions) -> Result<FrameIterator, Error> {
+    let stream = options.video_stream;
+    let file_name = path.to_str().ok_or(Error::Application(format!(
+        "{ERROR_OPENING_VIDEO}: the path is not valid UTF-8"
+    )))?;
+    let video = if stream == 0 {
+        VideoCapture::from_file(file_name, opencv::videoio::CAP_ANY)?
+    } else {
+        VideoCapture::from_file_with_params(
+            file_name,
+            opencv::videoio::CAP_ANY,
+            &opencv::core::Vector::from_slice(&[
+                opencv::videoio::CAP_PROP_VIDEO_STREAM,
+                stream as i32,
+            ]),
+        )?
+    };
 
     if video.is_opened()? {
-        Ok(FrameIterator::Video(video))
+        Ok(FrameIterator::Video {
+            capture: video,
+            read_retries: options.video_read_retries,
+        })
     } else {
         Err(Error::Application(ERROR_OPENING_VIDEO.to_string()))
     }
 }
 
-fn open_gif(path: &Path) -> Result<(FrameIterator, f64), Error> {
-    let file = File::open(path)
-        .map_err(|e| Error::Application(format!("{ERROR_OPENING_RESOURCE}: {e:?}")))?;
+fn open_gif(path: &Path, open_options: &OpenOptions) -> Result<(FrameIterator, f64), Error> {
+    let file = File::open(path)?;
     let mut options = gif::DecodeOptions::new();
     // https://lib.rs/crates/gif-dispose
     // for gif_dispose frame composing for rgba output, we need to set this as indexed.
     options.set_color_output(gif::ColorOutput::Indexed);
     let mut decoder = options
         .read_info(file)
-        .map_err(|e| Error::Application(format!("{ERROR_READING_GIF_HEADER}: {e:?}")))?;
+        .map_err(|e| Error::decode(ERROR_READING_GIF_HEADER, e))?;
 
     // delay is in units of 10ms, so we'll divide by 100.0, not 1000.0
     let mut delay: u64 = 0;
     let mut frames = Vec::new();
+    let mut delays = Vec::new();
     // The gif crate only exposes raw frame data that is not sufficient to render animated GIFs properly.
     // GIF requires special composing of frames which is non-trivial.
     let mut screen = gif_dispose::Screen::new_decoder(&decoder);
+    // every frame is composed into a full RGBA canvas
+    let frame_bytes = decoder.width() as usize * decoder.height() as usize * 4;
+    let mut decoded_bytes = 0;
     while let Ok(Some(frame)) = decoder.read_next_frame() {
+        decoded_bytes += frame_bytes;
+        if !open_options.fits_budget(decoded_bytes)? {
+            break;
+        }
         delay += frame.delay as u64;
+        delays.push(Duration::from_millis(frame.delay as u64 * 10));
         screen
             .blit_frame(frame)
-            .map_err(|e| Error::Application(format!("{ERROR_DECODING_IMAGE}: {e:?}")))?;
+            .map_err(|e| Error::decode(ERROR_DECODING_IMAGE, e))?;
         let (buf, width, height) = screen.pixels_rgba().to_contiguous_buf();
         frames.push(DynamicImage::ImageRgba8(image::RgbaImage::from_fn(
             width as u32,
@@ -188,25 +829,24 @@ fn open_gif(path: &Path) -> Result<(FrameIterator, f64), Error> {
         )));
     }
 
-    // fps is only an average across all frames, there is no per frame delay modelling
+    // fps is only an average across all frames, the exact timing is kept in `delays`
     let fps = frames.len() as f64 / (delay.max(1) as f64 / 100.0);
     Ok((
         FrameIterator::AnimatedImage {
             frames,
+            delays,
             current_frame: 0,
         },
         fps,
     ))
 }
 
-fn open_webp(path: &Path) -> Result<(FrameIterator, f64), Error> {
-    let mut file = File::open(path)
-        .map_err(|e| Error::Application(format!("{ERROR_OPENING_RESOURCE}: {e:?}")))?;
+fn open_webp(path: &Path, open_options: &OpenOptions) -> Result<(FrameIterator, f64), Error> {
+    let mut file = File::open(path)?;
     let mut buf = Vec::new();
     file.read_to_end(&mut buf)?;
     let mut frames = Vec::new();
-    let mut first_timestamp: i32 = i32::MAX;
-    let mut last_timestamp: i32 = i32::MIN;
+    let mut delays = Vec::new();
     // this code is based on the code example here:
     // https://developers.google.com/speed/webp/docs/container-api#webpanimdecoder_api
     unsafe {
@@ -226,36 +866,257 @@ fn open_webp(path: &Path) -> Result<(FrameIterator, f64), Error> {
         let mut info = webp::WebPAnimInfo::default();
         webp::WebPAnimDecoderGetInfo(dec, &mut info);
         let frame_sz = (info.canvas_width * info.canvas_height * 4) as usize;
-        for _ in 0..info.loop_count {
-            while webp::WebPAnimDecoderHasMoreFrames(dec) != 0 {
-                let mut buf: *mut u8 = std::ptr::null_mut();
-                let mut timestamp: i32 = 0;
-                webp::WebPAnimDecoderGetNext(dec, &mut buf, &mut timestamp);
-                first_timestamp = first_timestamp.min(timestamp);
-                last_timestamp = last_timestamp.max(timestamp);
-                if let Some(image) = image::RgbaImage::from_raw(
-                    info.canvas_width,
-                    info.canvas_height,
-                    std::slice::from_raw_parts(buf, frame_sz).to_vec(),
-                ) {
-                    frames.push(DynamicImage::ImageRgba8(image));
-                } else {
-                    // eprintln!("Failed to decode frame");
+        let mut decoded_bytes = 0;
+        let mut skipped = 0;
+        // decode a single pass, looping (even a finite `loop_count`) is up to
+        // `RenderOptions::loop_playback`
+        // timestamps mark the end of each frame, relative to the start of the animation
+        let mut previous_timestamp: i32 = 0;
+        while webp::WebPAnimDecoderHasMoreFrames(dec) != 0 {
+            let mut buf: *mut u8 = std::ptr::null_mut();
+            let mut timestamp: i32 = 0;
+            webp::WebPAnimDecoderGetNext(dec, &mut buf, &mut timestamp);
+            decoded_bytes += frame_sz;
+            match open_options.fits_budget(decoded_bytes) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(err) => {
+                    webp::WebPAnimDecoderDelete(dec);
+                    return Err(err);
                 }
             }
-            webp::WebPAnimDecoderReset(dec);
+            if let Some(image) = image::RgbaImage::from_raw(
+                info.canvas_width,
+                info.canvas_height,
+                std::slice::from_raw_parts(buf, frame_sz).to_vec(),
+            ) {
+                frames.push(DynamicImage::ImageRgba8(image));
+                delays.push(Duration::from_millis(
+                    timestamp.saturating_sub(previous_timestamp).max(0) as u64,
+                ));
+            } else {
+                skipped += 1;
+            }
+            previous_timestamp = timestamp;
         }
         webp::WebPAnimDecoderDelete(dec);
+        if skipped > 0 {
+            tracing::warn!(skipped, "skipped corrupt WebP frames");
+        }
+    }
+
+    // fps is only an average across all frames, the exact timing is kept in `delays`
+    let duration: Duration = delays.iter().sum();
+    let fps = frames.len() as f64 / duration.as_secs_f64().max(0.001);
+    Ok((
+        FrameIterator::AnimatedImage {
+            frames,
+            delays,
+            current_frame: 0,
+        },
+        fps,
+    ))
+}
+
+#[cfg(feature = "avif")]
+fn open_avif(path: &Path, open_options: &OpenOptions) -> Result<(FrameIterator, f64), Error> {
+    let mut file = File::open(path)?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+    let mut frames = Vec::new();
+    let mut delays = Vec::new();
+    // based on the decoding example of libavif:
+    // https://github.com/AOMediaCodec/libavif/blob/main/examples/avif_example_decode_memory.c
+    unsafe {
+        let dec = avif::avifDecoderCreate();
+        if dec.is_null() {
+            return Err(Error::Application(format!(
+                "{ERROR_DECODING_IMAGE}: cannot create AVIF decoder"
+            )));
+        }
+        let decoded = decode_avif_frames(dec, &buf, open_options, &mut frames, &mut delays);
+        avif::avifDecoderDestroy(dec);
+        decoded?;
+    }
+    if frames.is_empty() {
+        return Err(Error::Application(format!(
+            "{ERROR_DECODING_IMAGE}: no frames in {}",
+            path.display()
+        )));
     }
 
-    // fps is only an average across all frames, there is no per frame delay modelling
-    let fps = frames.len() as f64
-        / ((last_timestamp.saturating_sub(first_timestamp).max(1)) as f64 / 1000.0);
+    // fps is only an average across all frames, the exact timing is kept in `delays`
+    let duration: Duration = delays.iter().sum();
+    let fps = frames.len() as f64 / duration.as_secs_f64().max(0.001);
     Ok((
         FrameIterator::AnimatedImage {
             frames,
+            delays,
             current_frame: 0,
         },
         fps,
     ))
 }
+
+/// Decodes every frame of the AVIF in `buf` with `dec` as RGBA, stopping early when the
+/// frames exceed the budget of `open_options`.
+///
+/// # Safety
+///
+/// `dec` must be a valid decoder from `avifDecoderCreate`, it is not destroyed.
+#[cfg(feature = "avif")]
+unsafe fn decode_avif_frames(
+    dec: *mut avif::avifDecoder,
+    buf: &[u8],
+    open_options: &OpenOptions,
+    frames: &mut Vec<DynamicImage>,
+    delays: &mut Vec<Duration>,
+) -> Result<(), Error> {
+    let check = |result: avif::avifResult| {
+        if result == avif::AVIF_RESULT_OK {
+            return Ok(());
+        }
+        let message = unsafe { std::ffi::CStr::from_ptr(avif::avifResultToString(result)) };
+        Err(Error::decode(
+            ERROR_DECODING_IMAGE,
+            message.to_string_lossy().into_owned(),
+        ))
+    };
+
+    unsafe {
+        check(avif::avifDecoderSetIOMemory(dec, buf.as_ptr(), buf.len()))?;
+        check(avif::avifDecoderParse(dec))?;
+        let mut decoded_bytes = 0;
+        let mut skipped = 0;
+        while avif::avifDecoderNextImage(dec) == avif::AVIF_RESULT_OK {
+            let image = (*dec).image;
+            let (width, height) = ((*image).width, (*image).height);
+            decoded_bytes += width as usize * height as usize * 4;
+            if !open_options.fits_budget(decoded_bytes)? {
+                break;
+            }
+
+            let mut rgb = avif::avifRGBImage::default();
+            avif::avifRGBImageSetDefaults(&mut rgb, image);
+            rgb.format = avif::AVIF_RGB_FORMAT_RGBA;
+            rgb.depth = 8;
+            check(avif::avifRGBImageAllocatePixels(&mut rgb))?;
+            let converted = check(avif::avifImageYUVToRGB(image, &mut rgb));
+            // rows may be padded past `width * 4` bytes
+            let pixels = converted.ok().and_then(|_| {
+                let row_bytes = rgb.rowBytes as usize;
+                let raw = std::slice::from_raw_parts(rgb.pixels, row_bytes * height as usize);
+                let rows = raw.chunks_exact(row_bytes.max(1));
+                let pixels = rows.flat_map(|row| &row[..width as usize * 4]).copied();
+                image::RgbaImage::from_raw(width, height, pixels.collect())
+            });
+            avif::avifRGBImageFreePixels(&mut rgb);
+
+            match pixels {
+                Some(pixels) => {
+                    frames.push(DynamicImage::ImageRgba8(pixels));
+                    delays.push(Duration::from_secs_f64(
+                        (*dec).imageTiming.duration.max(0.0),
+                    ));
+                }
+                None => skipped += 1,
+            }
+        }
+        if skipped > 0 {
+            tracing::warn!(skipped, "skipped corrupt AVIF frames");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An icon with a PNG entry for every size and declared bit depth of `entries`, in that
+    /// order.
+    fn icon(entries: &[(u32, u8)]) -> Vec<u8> {
+        let images: Vec<Vec<u8>> = entries
+            .iter()
+            .map(|&(size, _)| {
+                let mut png = Vec::new();
+                DynamicImage::ImageRgba8(image::RgbaImage::new(size, size))
+                    .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+                    .unwrap();
+                png
+            })
+            .collect();
+
+        let mut icon = vec![0, 0, 1, 0, entries.len() as u8, 0];
+        let mut offset = 6 + 16 * entries.len();
+        for ((size, bits), png) in entries.iter().zip(&images) {
+            // width, height, no palette, reserved, one plane, bits per pixel
+            icon.extend_from_slice(&[*size as u8, *size as u8, 0, 0, 1, 0, *bits, 0]);
+            icon.extend_from_slice(&(png.len() as u32).to_le_bytes());
+            icon.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += png.len();
+        }
+        for png in images {
+            icon.extend_from_slice(&png);
+        }
+        icon
+    }
+
+    #[test]
+    fn icons_open_their_largest_entry() {
+        // the `image` decoder alone would pick the 16x16 entry for its color depth
+        let data = icon(&[(16, 32), (32, 24), (24, 24)]);
+        let largest = largest_ico_entry(&data).unwrap();
+        let img = image::load_from_memory_with_format(&largest, image::ImageFormat::Ico).unwrap();
+        assert_eq!((img.width(), img.height()), (32, 32));
+
+        let path = std::env::temp_dir().join(format!("iv2c-{}-icon.ico", std::process::id()));
+        std::fs::write(&path, &data).unwrap();
+        let media = open_media_from_path(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(media.unwrap().dimensions(), Some((32, 32)));
+    }
+
+    #[test]
+    fn skipping_stops_at_the_end_of_animations() {
+        let frames = vec![DynamicImage::new_rgb8(1, 1); 3];
+        let delays = [100, 200, 300].map(Duration::from_millis).to_vec();
+        let mut media = FrameIterator::AnimatedImage {
+            frames,
+            delays,
+            current_frame: 0,
+        };
+
+        // 350ms cover the first two frames and part of the third, which is still shown
+        media.skip_duration(Duration::from_millis(350), 10.0);
+        assert_eq!(media.position(), Some(2));
+        media.skip_duration(Duration::from_secs(10), 10.0);
+        assert_eq!(media.position(), Some(3));
+        assert!(media.next().is_none());
+
+        media.reset();
+        media.skip_frames(5);
+        assert_eq!(media.position(), Some(3));
+        assert!(media.next().is_none());
+    }
+
+    #[test]
+    fn unknown_files_without_video_are_unsupported() {
+        let path = std::env::temp_dir().join(format!("iv2c-{}-garbage.xyz", std::process::id()));
+        std::fs::write(&path, "not a video\n").unwrap();
+        let result = open_media_from_path(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        match result {
+            Err(Error::UnsupportedFormat(ext)) => assert_eq!(ext, "xyz"),
+            other => panic!("expected an unsupported format, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn read_retries_back_off() {
+        let backoffs: Vec<_> = (1..=4).map(retry_backoff).collect();
+        assert_eq!(backoffs, [5, 10, 20, 40].map(Duration::from_millis));
+        assert_eq!(retry_backoff(100), MAX_RETRY_BACKOFF);
+    }
+}
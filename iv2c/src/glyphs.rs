@@ -0,0 +1,79 @@
+//! Glyph shapes for structural character selection, see `RenderMode::Structural`.
+
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont, point};
+
+/// The font used to rasterize glyphs, for screenshots and the glyph atlas.
+pub(crate) const FONT_DATA: &[u8] = include_bytes!("JetBrainsMono-Regular.ttf");
+
+/// Height in pixels glyphs are rasterized at to measure their shape.
+const ATLAS_GLYPH_PX: f32 = 32.0;
+
+/// The ink coverage of every char of a char map in the four quadrants (top left, top right,
+/// bottom left, bottom right) of its cell, normalized so the densest quadrant is 1.
+#[derive(Debug, Clone)]
+pub struct GlyphAtlas {
+    patterns: Vec<(char, [f32; 4])>,
+}
+
+impl GlyphAtlas {
+    /// Rasterizes every char of `chars` once. Chars missing from the font are treated as empty.
+    pub fn new(chars: &[char]) -> Self {
+        let font = FontRef::try_from_slice(FONT_DATA).expect("bundled font is valid");
+        let scaled = font.as_scaled(PxScale::from(ATLAS_GLYPH_PX));
+        let cell_w = scaled.h_advance(font.glyph_id('M')).max(1.0);
+        let cell_h = ATLAS_GLYPH_PX;
+
+        let mut patterns: Vec<(char, [f32; 4])> = chars
+            .iter()
+            .map(|&c| {
+                let mut coverage = [0_f32; 4];
+                let glyph = font
+                    .glyph_id(c)
+                    .with_scale_and_position(ATLAS_GLYPH_PX, point(0.0, scaled.ascent()));
+                if let Some(outlined) = font.outline_glyph(glyph) {
+                    let bounds = outlined.px_bounds();
+                    outlined.draw(|x, y, ink| {
+                        let x = bounds.min.x + x as f32;
+                        let y = bounds.min.y + y as f32;
+                        if (0.0..cell_w).contains(&x) && (0.0..cell_h).contains(&y) {
+                            let quadrant =
+                                (2.0 * y / cell_h) as usize * 2 + (2.0 * x / cell_w) as usize;
+                            coverage[quadrant] += ink;
+                        }
+                    });
+                }
+                let quadrant_area = cell_w * cell_h / 4.0;
+                (c, coverage.map(|ink| ink / quadrant_area))
+            })
+            .collect();
+
+        let densest = patterns
+            .iter()
+            .flat_map(|(_, pattern)| *pattern)
+            .fold(0_f32, f32::max);
+        if densest > 0.0 {
+            for (_, pattern) in &mut patterns {
+                *pattern = pattern.map(|ink| ink / densest);
+            }
+        }
+
+        Self { patterns }
+    }
+
+    /// The char whose shape is closest (least sum of squared differences) to `pattern`, the
+    /// luminance of the four quadrants of a cell in `0.0..=1.0`.
+    pub fn best_match(&self, pattern: [f32; 4]) -> Option<char> {
+        self.patterns
+            .iter()
+            .map(|(c, glyph)| {
+                let ssd: f32 = glyph
+                    .iter()
+                    .zip(pattern)
+                    .map(|(ink, lum)| (ink - lum) * (ink - lum))
+                    .sum();
+                (*c, ssd)
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(c, _)| c)
+    }
+}
@@ -1,6 +1,67 @@
+use std::path::Path;
+
+use image::DynamicImage;
+
+use crate::{
+    error::{ERROR_DATA, Error},
+    maps::CharMap,
+    pipeline::{ImagePipeline, Resolution},
+    render::RenderFrame,
+};
+
+pub mod clock;
+pub mod color;
 pub mod error;
 pub mod frames;
+#[cfg(feature = "render")]
+pub mod glyphs;
 pub mod maps;
 pub mod pipeline;
 pub mod render;
+pub(crate) mod tone;
 pub(crate) mod util;
+#[cfg(feature = "ratatui")]
+pub mod widget;
+
+/// Converts the first frame of the media at `path` to plain text (no colors),
+/// `width` x `height` characters with rows separated by `\n`.
+pub fn to_ascii_string(
+    path: impl AsRef<Path>,
+    width: u32,
+    height: u32,
+    char_map: CharMap,
+) -> Result<String, Error> {
+    let mut media = frames::open_media_from_path(path.as_ref())?;
+    let image = media
+        .frame_iter
+        .next()
+        .ok_or(Error::Application(ERROR_DATA.to_string()))?;
+    to_ascii_string_from_image(&image, width, height, char_map)
+}
+
+/// Converts `image` to plain text (no colors), `width` x `height` characters
+/// with rows separated by `\n`.
+pub fn to_ascii_string_from_image(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    char_map: CharMap,
+) -> Result<String, Error> {
+    let pipeline = ImagePipeline::new(Resolution::Fixed(width, height), char_map, true)?;
+    let resized = pipeline.resize(image)?;
+    let text = pipeline.to_ascii(&resized.into_luma8());
+
+    Ok(text.lines().collect::<Vec<_>>().join("\n"))
+}
+
+/// Converts `image` to a colored frame of `cols` x `rows` characters using `map`.
+/// Shorthand for running an `ImagePipeline` with the default settings once.
+pub fn image_to_art(
+    image: &DynamicImage,
+    cols: u32,
+    rows: u32,
+    map: CharMap,
+) -> Result<RenderFrame, Error> {
+    let mut pipeline = ImagePipeline::new(Resolution::Fixed(cols, rows), map, true)?;
+    RenderFrame::from_image(&mut pipeline, image)
+}
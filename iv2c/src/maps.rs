@@ -1,3 +1,7 @@
+use unicode_width::UnicodeWidthChar;
+
+use crate::error::{ERROR_EMPTY_CHAR_MAP, Error};
+
 pub enum CharMap {
     Chars1,
     Chars2,
@@ -5,6 +9,7 @@ pub enum CharMap {
     Solid,
     Dotted,
     Gradient,
+    Gradient24,
     BlackWhite,
     BwDotted,
     Braille,
@@ -20,6 +25,7 @@ impl CharMap {
             CharMap::Solid => SOLID.chars().collect(),
             CharMap::Dotted => DOTTED.chars().collect(),
             CharMap::Gradient => GRADIENT.chars().collect(),
+            CharMap::Gradient24 => GRADIENT24.chars().collect(),
             CharMap::BlackWhite => BLACKWHITE.chars().collect(),
             CharMap::BwDotted => BW_DOTTED.chars().collect(),
             CharMap::Braille => BRAILLE.chars().collect(),
@@ -27,9 +33,53 @@ impl CharMap {
         }
     }
 
-    pub fn custom(chars: &str) -> Self {
-        Self::Custom(chars.chars().collect())
+    /// The built-in maps (all but `Custom`), in the order players cycle through them.
+    pub fn built_ins() -> [CharMap; 10] {
+        [
+            CharMap::Dotted,
+            CharMap::Gradient,
+            CharMap::Chars3,
+            CharMap::Braille,
+            CharMap::Chars1,
+            CharMap::Chars2,
+            CharMap::Gradient24,
+            CharMap::Solid,
+            CharMap::BlackWhite,
+            CharMap::BwDotted,
+        ]
+    }
+
+    /// A short name for display.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CharMap::Chars1 => "chars1",
+            CharMap::Chars2 => "chars2",
+            CharMap::Chars3 => "chars3",
+            CharMap::Solid => "solid",
+            CharMap::Dotted => "dotted",
+            CharMap::Gradient => "gradient",
+            CharMap::Gradient24 => "gradient24",
+            CharMap::BlackWhite => "blackwhite",
+            CharMap::BwDotted => "bwdotted",
+            CharMap::Braille => "braille",
+            CharMap::Custom(_) => "custom",
+        }
     }
+
+    /// Creates a custom char map, control characters (e.g. newlines) are stripped.
+    /// Fails if no visible character remains.
+    pub fn custom(chars: &str) -> Result<Self, Error> {
+        let chars: Vec<char> = chars.chars().filter(|c| !c.is_control()).collect();
+        if chars.iter().all(|c| c.is_whitespace()) {
+            return Err(Error::Pipeline(ERROR_EMPTY_CHAR_MAP.to_string()));
+        }
+        Ok(Self::Custom(chars))
+    }
+}
+
+/// Number of terminal cells `c` occupies (e.g. 2 for CJK and most emoji).
+pub fn display_width(c: char) -> usize {
+    c.width().unwrap_or(0)
 }
 
 // maps from https://github.com/maxcurzi/tplay/blob/main/src/pipeline/char_maps.rs
@@ -43,6 +93,9 @@ const CHARS3: &str = r##" `.-':_,^=;><+!rc*/z?sLTv)J7(|Fi{C}fI31tlu[neoZ5Yxjya]2
 const SOLID: &str = r#"█"#; // 1 Solid block
 const DOTTED: &str = r#"⣿"#; // 1 dotted block
 const GRADIENT: &str = r#" ░▒▓█"#; // 5 chars
+// ordered by the ink coverage of the bundled font (0 to 0.23 for the punctuation, then the
+// eighth blocks), denser steps in the shadows where brightness differences are most visible
+const GRADIENT24: &str = r##" `.-,_:;~"!=+*#%$▂▃▄▅▆▇█"##; // 24 chars
 const BLACKWHITE: &str = r#" █"#; // 2 chars
 const BW_DOTTED: &str = r#" ⣿"#; // 2 dotted block
 const BRAILLE: &str = r#" ··⣀⣀⣤⣤⣤⣀⡀⢀⠠⠔⠒⠑⠊⠉⠁"#; // 16 chars (braille-based)
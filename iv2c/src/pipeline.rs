@@ -1,23 +1,235 @@
-use crate::{error::*, maps::CharMap};
+#[cfg(feature = "render")]
+use crate::glyphs::GlyphAtlas;
+use crate::{
+    color::{Colormap, Palette},
+    error::*,
+    maps::{CharMap, display_width},
+    tone,
+};
 use fast_image_resize as fir;
-use image::{DynamicImage, GrayImage};
+use image::{DynamicImage, GrayImage, Pixel, Rgb, RgbImage};
+use std::{borrow::Cow, collections::VecDeque, ops::RangeInclusive};
 
 pub enum Resolution {
     /// Use fixed resolution (width, height)
     Fixed(u32, u32),
     /// Use a divisor to scale resolution while preserving aspect ratio
     Divisor(u32),
+    /// Use a fixed width, the height follows the aspect ratio of the source so tall images
+    /// overflow instead of shrinking to fit
+    FitWidth(u32),
 }
 
+/// How many times taller than wide a terminal cell is, to keep the aspect ratio of
+/// `Resolution::FitWidth`.
+const CELL_ASPECT: f64 = 2.0;
+
 impl Resolution {
+    /// The output size for `img`, at least 1x1 so tiny terminals or a divisor larger than
+    /// the image still produce a frame.
     pub fn calc(&self, img: &DynamicImage) -> (u32, u32) {
-        match self {
+        self.calc_for(img.width(), img.height())
+    }
+
+    /// Like `calc`, for an image of `width` x `height` pixels.
+    pub fn calc_for(&self, width: u32, height: u32) -> (u32, u32) {
+        let (w, h) = match self {
             Resolution::Fixed(w, h) => (*w, *h),
-            Resolution::Divisor(d) => (img.width() / d, img.height() / d),
+            Resolution::Divisor(d) => {
+                let d = (*d).max(1);
+                (width / d, height / d)
+            }
+            Resolution::FitWidth(w) => {
+                let rows = *w as f64 * height as f64 / width.max(1) as f64 / CELL_ASPECT;
+                (*w, rows.round() as u32)
+            }
+        };
+        (w.max(1), h.max(1))
+    }
+}
+
+/// A region of the source in pixels, applied before resizing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Crop {
+    /// Clamps the region to an image of `width` x `height`, keeping at least one pixel.
+    pub fn clamp(&self, width: u32, height: u32) -> Crop {
+        let x = self.x.min(width.saturating_sub(1));
+        let y = self.y.min(height.saturating_sub(1));
+        Crop {
+            x,
+            y,
+            width: self.width.min(width - x).max(1),
+            height: self.height.min(height - y).max(1),
+        }
+    }
+}
+
+/// Cells of a frame in rows of `width`, indexed by (x, y) instead of parsing lines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Grid<T> {
+    pub width: usize,
+    pub height: usize,
+    /// The cells row by row, `width * height` of them.
+    pub cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    /// The cell at column `x` of row `y`, `None` outside the grid.
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        if x >= self.width {
+            return None;
+        }
+        self.cells.get(y * self.width + x)
+    }
+
+    /// Iterates over the rows of cells.
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        self.cells.chunks(self.width.max(1))
+    }
+}
+
+/// Automatic cropping of uniform dark borders (letterboxing) before resizing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AutoCrop {
+    /// Keep the borders.
+    #[default]
+    Off,
+    /// Detect the borders over this many frames, then keep cropping to the content found in
+    /// any of them so the crop stays stable while playing.
+    Frames(u32),
+}
+
+/// Which source pixels a cell is computed from when downscaling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SamplePoint {
+    /// The mean of all source pixels the cell covers (box filter). Fine detail and thin lines
+    /// still show up as a lighter or darker cell instead of flickering in and out, at the cost
+    /// of reading every source pixel.
+    #[default]
+    Area,
+    /// The single source pixel at the center of the cell (nearest neighbour). Faster on large
+    /// sources and keeps hard edges crisp, but aliases badly on detail smaller than a cell.
+    Center,
+}
+
+/// Rows and columns whose brightest pixel is at most this luminance count as border.
+const BORDER_LUMA: u8 = 32;
+
+/// A char map used instead of the main one for luminance within `range`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LumaBand {
+    pub range: RangeInclusive<u8>,
+    pub char_map: Vec<char>,
+}
+
+/// A tone curve mapping the luminance through control points, linearly interpolated between
+/// them. Gives full control over brightness, contrast and gamma, e.g. an S-curve.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToneCurve {
+    points: Vec<(u8, u8)>,
+    lut: [u8; 256],
+}
+
+impl ToneCurve {
+    /// Creates a curve through `points` (input, output), which need at least two points with
+    /// strictly increasing inputs.
+    pub fn new(points: Vec<(u8, u8)>) -> Result<Self, Error> {
+        if points.len() < 2 {
+            return Err(Error::Application(
+                "A tone curve needs at least two points".to_string(),
+            ));
+        }
+        if let Some(pair) = points.windows(2).find(|pair| pair[0].0 >= pair[1].0) {
+            return Err(Error::Application(format!(
+                "Tone curve points must have increasing inputs, {} is followed by {}",
+                pair[0].0, pair[1].0
+            )));
         }
+        let lut = tone::curve_lut(&points);
+        Ok(Self { points, lut })
+    }
+
+    /// Parses a comma separated list of `input:output` points, e.g. `0:0,128:200,255:255`.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let points = s
+            .split(',')
+            .map(|point| {
+                point
+                    .split_once(':')
+                    .and_then(|(input, output)| {
+                        Some((input.trim().parse().ok()?, output.trim().parse().ok()?))
+                    })
+                    .ok_or_else(|| {
+                        Error::Application(format!(
+                            "Invalid tone curve point `{point}`, expected IN:OUT (0-255)"
+                        ))
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Self::new(points)
+    }
+
+    /// The control points of the curve.
+    pub fn points(&self) -> &[(u8, u8)] {
+        &self.points
+    }
+
+    /// The output of the curve for every luminance.
+    pub fn lut(&self) -> &[u8; 256] {
+        &self.lut
     }
 }
 
+/// Automatic level stretch, spreading the luminance between the 2nd and 98th percentile
+/// across the full range before the char map lookup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AutoLevels {
+    /// Use the luminance as is.
+    #[default]
+    Off,
+    /// Compute the levels once from the first calibrated frame (no flicker).
+    FirstFrame,
+    /// Recompute the levels for every calibrated frame.
+    PerFrame,
+    /// Recompute the levels for every calibrated frame, averaged over the last this many
+    /// frames so they follow the content without flickering.
+    Rolling(u32),
+}
+
+/// Histogram equalization applied to the luminance before the char map lookup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Equalization {
+    /// Map luminance linearly onto the char map.
+    #[default]
+    Off,
+    /// Compute the lookup table once from the first calibrated frame (no flicker).
+    FirstFrame,
+    /// Recompute the lookup table for every calibrated frame.
+    Rolling,
+}
+
+/// What a cell of the output is made of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    /// Pick a char from the char map by luminance.
+    #[default]
+    Chars,
+    /// Emit only spaces, the colors fill the cell backgrounds like a low-res framebuffer.
+    Blocks,
+    /// Pick the char whose rasterized glyph best matches the 2x2 luminance pattern of the
+    /// cell, which follows edges and text much better than a luminance ramp. Too slow for
+    /// realtime playback, meant for exports.
+    #[cfg(feature = "render")]
+    Structural,
+}
+
 pub struct ImagePipeline {
     /// The target resolution (width and height) for the pipeline.
     pub resolution: Resolution,
@@ -25,63 +237,708 @@ pub struct ImagePipeline {
     pub char_map: Vec<char>,
     /// Whether to add newlines to the output at the end of each line
     pub new_lines: bool,
+    /// Char maps for luminance bands, taking precedence over `char_map`.
+    pub bands: Vec<LumaBand>,
+    /// The histogram equalization mode.
+    pub equalization: Equalization,
+    /// The automatic level stretch mode.
+    pub auto_levels: AutoLevels,
+    /// Whether cells are chars or background-filled blocks.
+    pub mode: RenderMode,
+    /// Region of the source to convert, `None` for the whole image.
+    pub crop: Option<Crop>,
+    /// Border detection mode, ignored when `crop` is set.
+    pub autocrop: AutoCrop,
+    /// How cells sample the source when downscaling.
+    pub sample_point: SamplePoint,
+    /// How strongly detailed areas (high local variance) are pushed towards denser glyphs,
+    /// `0.0` disables it. Around `0.5` keeps flat areas as they are while textures read better.
+    pub detail: f32,
+    /// Whether to leave out the colors of frames, which grayscale output doesn't use. Blocks
+    /// keep them since they only show through their colors.
+    pub grayscale: bool,
+    /// Palette the output colors are snapped to, `None` keeps them as they are.
+    pub palette: Option<Palette>,
+    /// Whether to dither the colors when snapping them to `palette`.
+    pub palette_dither: bool,
+    /// Colormap the luminance of the cells is colored with instead of their source colors, see
+    /// `set_colormap`.
+    colormap: Option<(Colormap, [[u8; 3]; 256])>,
+    /// Cells whose alpha (averaged like the colors) is below this become `transparent_char`
+    /// regardless of their luminance, so sprites keep their outline. `None` maps them by the
+    /// luminance composited over `background`.
+    pub alpha_threshold: Option<u8>,
+    /// The char transparent cells are drawn with, see `alpha_threshold`.
+    pub transparent_char: char,
+    /// Color transparent sources are composited over, should match the terminal background so
+    /// fully transparent pixels end up as the darkest (empty) char.
+    pub background: [u8; 3],
+    /// Composite transparent sources over a two-tone checkerboard instead of `background`, to
+    /// make their transparency visible like image editors do.
+    pub checker: bool,
+    /// Sets the background of every cell to its color darkened by this factor (0.0 black, 1.0
+    /// the color itself), so glyphs keep their contrast in dark scenes. `None` leaves the
+    /// background to the terminal.
+    pub bg_shade: Option<f32>,
+    /// Cells only change when their luminance moved more than this from the luminance of their
+    /// last char, see `stabilize`. `None` follows every change.
+    pub stabilize: Option<u8>,
+    /// Keep the alpha of every cell in `RenderFrame::alphas`, so images rendered from the frames
+    /// keep the transparency of the source.
+    pub keep_alpha: bool,
+    /// Level stretch lookup table computed by `calibrate`.
+    levels_lut: Option<[u8; 256]>,
+    /// Levels of the last frames for `AutoLevels::Rolling`.
+    levels_window: VecDeque<(usize, usize)>,
+    /// Equalization lookup table computed by `calibrate`, applied after the levels.
+    equalization_lut: Option<[u8; 256]>,
+    /// Tone curve applied to the luminance after the levels and equalization.
+    tone_curve: Option<ToneCurve>,
+    /// Combined luminance lookup table applied by `to_ascii`.
+    luma_lut: Option<[u8; 256]>,
+    /// Content region found by `detect_borders` so far.
+    border_crop: Option<Crop>,
+    /// Number of frames `detect_borders` has looked at.
+    border_frames: u32,
+    /// Resizer and output buffer reused by `resize_cached` from frame to frame.
+    resize_cache: (fir::Resizer, RgbImage),
+    /// Luminance of the cells of the last frame passed to `stabilize`.
+    stable_luma: Option<GrayImage>,
+    /// Glyph shapes of the char map, present in structural mode.
+    #[cfg(feature = "render")]
+    glyph_atlas: Option<GlyphAtlas>,
 }
 
 impl ImagePipeline {
-    pub fn new(resolution: Resolution, char_map: CharMap, new_lines: bool) -> Self {
-        Self {
-            resolution,
-            char_map: char_map.chars(),
-            new_lines,
-        }
+    pub fn new(resolution: Resolution, char_map: CharMap, new_lines: bool) -> Result<Self, Error> {
+        Self::builder()
+            .resolution(resolution)
+            .char_map(char_map)
+            .new_lines(new_lines)
+            .build()
+    }
+
+    /// Returns a builder to configure a pipeline setting by setting.
+    pub fn builder() -> ImagePipelineBuilder {
+        ImagePipelineBuilder::default()
     }
 
     pub fn set_resolution(&mut self, resolution: Resolution) -> &mut Self {
         self.resolution = resolution;
+        // the buffer of the old size would only be reused once the new size is known
+        self.resize_cache.1 = RgbImage::default();
+        self
+    }
+
+    /// Replaces the main char map, empty maps are ignored.
+    pub fn set_char_map(&mut self, char_map: CharMap) -> &mut Self {
+        let char_map = char_map.chars();
+        if char_map.is_empty() {
+            return self;
+        }
+        #[cfg(feature = "render")]
+        if self.glyph_atlas.is_some() {
+            self.glyph_atlas = Some(GlyphAtlas::new(&char_map));
+        }
+        self.char_map = char_map;
+        self
+    }
+
+    pub fn set_equalization(&mut self, equalization: Equalization) -> &mut Self {
+        self.equalization = equalization;
+        self.equalization_lut = None;
+        self.update_luma_lut();
+        self
+    }
+
+    pub fn set_auto_levels(&mut self, auto_levels: AutoLevels) -> &mut Self {
+        self.auto_levels = auto_levels;
+        self.levels_lut = None;
+        self.levels_window.clear();
+        self.update_luma_lut();
+        self
+    }
+
+    pub fn set_mode(&mut self, mode: RenderMode) -> &mut Self {
+        self.mode = mode;
+        #[cfg(feature = "render")]
+        if mode == RenderMode::Structural && self.glyph_atlas.is_none() {
+            self.glyph_atlas = Some(GlyphAtlas::new(&self.char_map));
+        }
+        self
+    }
+
+    pub fn set_crop(&mut self, crop: Option<Crop>) -> &mut Self {
+        self.crop = crop;
+        self
+    }
+
+    pub fn set_sample_point(&mut self, sample_point: SamplePoint) -> &mut Self {
+        self.sample_point = sample_point;
+        self
+    }
+
+    pub fn set_autocrop(&mut self, autocrop: AutoCrop) -> &mut Self {
+        self.autocrop = autocrop;
+        self.border_crop = None;
+        self.border_frames = 0;
+        self
+    }
+
+    pub fn set_detail(&mut self, detail: f32) -> &mut Self {
+        self.detail = detail;
+        self
+    }
+
+    pub fn set_grayscale(&mut self, grayscale: bool) -> &mut Self {
+        self.grayscale = grayscale;
         self
     }
 
+    pub fn set_palette(&mut self, palette: Option<Palette>) -> &mut Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Colors every cell by its luminance through `colormap`, `None` uses the source colors.
+    pub fn set_colormap(&mut self, colormap: Option<Colormap>) -> &mut Self {
+        self.colormap = colormap.map(|colormap| (colormap, colormap.lut()));
+        self
+    }
+
+    /// The colormap set with `set_colormap`.
+    pub fn colormap(&self) -> Option<Colormap> {
+        self.colormap.map(|(colormap, _)| colormap)
+    }
+
+    /// Lookup table from luminance to color of the colormap, if one is set.
+    pub(crate) fn colormap_lut(&self) -> Option<&[[u8; 3]; 256]> {
+        self.colormap.as_ref().map(|(_, lut)| lut)
+    }
+
+    pub fn set_palette_dither(&mut self, palette_dither: bool) -> &mut Self {
+        self.palette_dither = palette_dither;
+        self
+    }
+
+    pub fn set_background(&mut self, background: [u8; 3]) -> &mut Self {
+        self.background = background;
+        self
+    }
+
+    pub fn set_checker(&mut self, checker: bool) -> &mut Self {
+        self.checker = checker;
+        self
+    }
+
+    pub fn set_bg_shade(&mut self, bg_shade: Option<f32>) -> &mut Self {
+        self.bg_shade = bg_shade;
+        self
+    }
+
+    pub fn set_keep_alpha(&mut self, keep_alpha: bool) -> &mut Self {
+        self.keep_alpha = keep_alpha;
+        self
+    }
+
+    pub fn set_stabilize(&mut self, stabilize: Option<u8>) -> &mut Self {
+        self.stabilize = stabilize;
+        self.stable_luma = None;
+        self
+    }
+
+    /// Maps the luminance through `tone_curve` before the char map lookup, `None` removes it.
+    pub fn set_tone_curve(&mut self, tone_curve: Option<ToneCurve>) -> &mut Self {
+        self.tone_curve = tone_curve;
+        self.update_luma_lut();
+        self
+    }
+
+    /// The tone curve set with `set_tone_curve`.
+    pub fn tone_curve(&self) -> Option<&ToneCurve> {
+        self.tone_curve.as_ref()
+    }
+
+    /// Keeps the luminance of the last frame for cells of `cells` that moved at most
+    /// `stabilize` from it, so noise doesn't flip them between neighbouring chars every frame.
+    /// Call it once per frame, before the chars are picked.
+    pub fn stabilize(&mut self, cells: &mut GrayImage) {
+        let Some(threshold) = self.stabilize else {
+            self.stable_luma = None;
+            return;
+        };
+        if let Some(stable) = &mut self.stable_luma
+            && stable.dimensions() == cells.dimensions()
+        {
+            for (cell, stable) in cells.pixels_mut().zip(stable.pixels_mut()) {
+                if cell[0].abs_diff(stable[0]) <= threshold {
+                    *cell = *stable;
+                } else {
+                    *stable = *cell;
+                }
+            }
+        } else {
+            self.stable_luma = Some(cells.clone());
+        }
+    }
+
+    /// Samples the luma histogram of `input` and updates the lookup table used by `to_ascii`,
+    /// according to the configured `AutoLevels` and `Equalization`.
+    pub fn calibrate(&mut self, input: &GrayImage) {
+        let update_levels = match self.auto_levels {
+            AutoLevels::Off => {
+                self.levels_lut = None;
+                false
+            }
+            AutoLevels::FirstFrame => self.levels_lut.is_none(),
+            AutoLevels::PerFrame | AutoLevels::Rolling(_) => true,
+        };
+        let update_equalization = match self.equalization {
+            Equalization::Off => {
+                self.equalization_lut = None;
+                false
+            }
+            Equalization::FirstFrame => self.equalization_lut.is_none(),
+            Equalization::Rolling => true,
+        };
+
+        if update_levels || update_equalization {
+            let histogram = tone::histogram(input);
+            if update_levels {
+                let (lo, hi) = tone::percentiles(&histogram, 0.02, 0.98);
+                let (lo, hi) = match self.auto_levels {
+                    AutoLevels::Rolling(frames) => {
+                        if self.levels_window.len() >= frames.max(1) as usize {
+                            self.levels_window.pop_front();
+                        }
+                        self.levels_window.push_back((lo, hi));
+                        let n = self.levels_window.len();
+                        let (lo_sum, hi_sum) = self
+                            .levels_window
+                            .iter()
+                            .fold((0, 0), |(l, h), (lo, hi)| (l + lo, h + hi));
+                        (lo_sum / n, hi_sum / n)
+                    }
+                    _ => (lo, hi),
+                };
+                self.levels_lut = Some(tone::stretch_lut(lo, hi));
+            }
+            if update_equalization {
+                // equalize what is left after the level stretch
+                let histogram = tone::remap_histogram(&histogram, self.levels_lut);
+                self.equalization_lut = Some(tone::equalization_lut(&histogram));
+            }
+        }
+        self.update_luma_lut();
+    }
+
+    /// Combines the levels, equalization and tone curve into the table `to_ascii` applies.
+    fn update_luma_lut(&mut self) {
+        let levels = tone::compose(self.levels_lut, self.equalization_lut);
+        self.luma_lut = tone::compose(levels, self.tone_curve.as_ref().map(|curve| *curve.lut()));
+    }
+
+    /// Looks for dark borders around `img` while `autocrop` still samples frames, widening the
+    /// region `resize` crops to so it covers the content of every sampled frame. Frames that are
+    /// dark all over (e.g. fades) don't change it.
+    pub fn detect_borders(&mut self, img: &DynamicImage) {
+        let AutoCrop::Frames(frames) = self.autocrop else {
+            self.border_crop = None;
+            return;
+        };
+        if self.border_frames >= frames || img.width() == 0 || img.height() == 0 {
+            return;
+        }
+        self.border_frames += 1;
+
+        let luma = img.to_luma8();
+        let (width, height) = luma.dimensions();
+        let row_is_border = |y: u32| (0..width).all(|x| luma.get_pixel(x, y)[0] <= BORDER_LUMA);
+        let column_is_border = |x: u32| (0..height).all(|y| luma.get_pixel(x, y)[0] <= BORDER_LUMA);
+        let Some(top) = (0..height).find(|&y| !row_is_border(y)) else {
+            return;
+        };
+        let bottom = (0..height)
+            .rev()
+            .find(|&y| !row_is_border(y))
+            .unwrap_or(top);
+        let left = (0..width).find(|&x| !column_is_border(x)).unwrap_or(0);
+        let right = (0..width)
+            .rev()
+            .find(|&x| !column_is_border(x))
+            .unwrap_or(left);
+
+        let (left, top, right, bottom) = match self.border_crop {
+            Some(crop) => (
+                left.min(crop.x),
+                top.min(crop.y),
+                right.max(crop.x + crop.width - 1),
+                bottom.max(crop.y + crop.height - 1),
+            ),
+            None => (left, top, right, bottom),
+        };
+        self.border_crop = Some(Crop {
+            x: left,
+            y: top,
+            width: right - left + 1,
+            height: bottom - top + 1,
+        });
+    }
+
+    /// Number of terminal cells every char of the output occupies, the widest glyph of the
+    /// char map. Narrower glyphs are padded with spaces by `to_ascii` to keep columns aligned.
+    pub fn cell_width(&self) -> usize {
+        if self.mode == RenderMode::Blocks {
+            return 1;
+        }
+        let transparent = self.alpha_threshold.map(|_| self.transparent_char);
+        self.char_map
+            .iter()
+            .chain(self.bands.iter().flat_map(|band| &band.char_map))
+            .chain(&transparent)
+            .map(|&c| display_width(c))
+            .max()
+            .unwrap_or(1)
+            .max(1)
+    }
+
     pub fn resize(&self, img: &DynamicImage) -> Result<DynamicImage, Error> {
-        let width = img.width();
-        let height = img.height();
-        let src_image = fir::images::Image::from_vec_u8(
-            width,
-            height,
-            img.to_owned().into_rgb8().to_vec(),
+        self.resize_scaled(img, 1)
+    }
+
+    /// Like `resize`, but into a buffer that is reused for the next frame while the output
+    /// size stays the same, instead of allocating a new one every frame. Returns the luminance
+    /// of the resized frame, its colors are available from `resized` until the next call.
+    pub(crate) fn resize_cached(&mut self, img: &DynamicImage) -> Result<GrayImage, Error> {
+        let (mut resizer, cached) = std::mem::take(&mut self.resize_cache);
+        if self.skips_colors() {
+            // nothing reads the colors, resize the luminance alone
+            let mut cells = Vec::new();
+            let resized = self.resize_raw(
+                img,
+                &self.luma_pixels(img),
+                1,
+                fir::PixelType::U8,
+                &mut resizer,
+                &mut cells,
+            );
+            self.resize_cache = (resizer, cached);
+            let (dst_w, dst_h) = resized?;
+            return GrayImage::from_raw(dst_w, dst_h, cells)
+                .ok_or(Error::Pipeline(ERROR_DATA.to_string()));
+        }
+        let mut buffer = cached.into_raw();
+        let (dst_w, dst_h) = self.resize_raw(
+            img,
+            &self.rgb_pixels(img),
+            1,
             fir::PixelType::U8x3,
+            &mut resizer,
+            &mut buffer,
+        )?;
+        let resized = RgbImage::from_raw(dst_w, dst_h, buffer)
+            .ok_or(Error::Pipeline(ERROR_DATA.to_string()))?;
+        // the same conversion callers of `resize` use, moving the buffer in and out
+        let resized = DynamicImage::ImageRgb8(resized);
+        let luma = resized.to_luma8();
+        self.resize_cache = (resizer, resized.into_rgb8());
+        Ok(luma)
+    }
+
+    /// The frame last resized by `resize_cached`, unless it skipped the colors.
+    pub(crate) fn resized(&self) -> &RgbImage {
+        &self.resize_cache.1
+    }
+
+    /// Whether rendered frames leave out their colors: grayscale output without a colormap,
+    /// except blocks, which only show through their colors.
+    pub(crate) fn skips_colors(&self) -> bool {
+        self.grayscale && self.mode != RenderMode::Blocks && self.colormap.is_none()
+    }
+
+    /// Like `resize`, but with `scale` x `scale` pixels per cell.
+    fn resize_scaled(&self, img: &DynamicImage, scale: u32) -> Result<DynamicImage, Error> {
+        let mut resized = Vec::new();
+        let (dst_w, dst_h) = self.resize_raw(
+            img,
+            &self.rgb_pixels(img),
+            scale,
+            fir::PixelType::U8x3,
+            &mut fir::Resizer::new(),
+            &mut resized,
+        )?;
+        let img_buff = RgbImage::from_vec(dst_w, dst_h, resized)
+            .ok_or(Error::Pipeline(ERROR_DATA.to_string()))?;
+
+        Ok(DynamicImage::ImageRgb8(img_buff))
+    }
+
+    /// The RGB pixels of `img`, borrowed for RGB sources and converted once for others.
+    fn rgb_pixels<'a>(&self, img: &'a DynamicImage) -> Cow<'a, [u8]> {
+        if let DynamicImage::ImageRgb8(rgb) = img {
+            return Cow::Borrowed(rgb.as_raw());
+        }
+        if !self.checker || !img.color().has_alpha() {
+            return Cow::Owned(flatten(img, |_, _| self.background).into_raw());
+        }
+
+        // squares of two cells side by side, which look square with cells twice as tall as wide
+        let (crop, cols, rows) = self.output_size(img.width(), img.height(), 1);
+        let square_w = (2 * crop.width / cols).max(1);
+        let square_h = (crop.height / rows).max(1);
+        let checker = |x: u32, y: u32| {
+            let (x, y) = (x.saturating_sub(crop.x), y.saturating_sub(crop.y));
+            CHECKER_COLORS[((x / square_w + y / square_h) % 2) as usize]
+        };
+        Cow::Owned(flatten(img, checker).into_raw())
+    }
+
+    /// The luminance of the pixels `rgb_pixels` returns, borrowed for grayscale sources.
+    fn luma_pixels<'a>(&self, img: &'a DynamicImage) -> Cow<'a, [u8]> {
+        if let DynamicImage::ImageLuma8(luma) = img {
+            return Cow::Borrowed(luma.as_raw());
+        }
+        let rgb = self.rgb_pixels(img);
+        Cow::Owned(
+            rgb.chunks_exact(3)
+                .map(|pixel| Rgb([pixel[0], pixel[1], pixel[2]]).to_luma()[0])
+                .collect(),
         )
-        .map_err(|err| Error::Pipeline(format!("{ERROR_RESIZE}:{err:?}")))?;
+    }
 
-        let (dst_w, dst_h) = self.resolution.calc(img);
-        let mut dst_image = fir::images::Image::new(dst_w, dst_h, fir::PixelType::U8x3);
+    /// The alpha channel of `img` resized like `resize` does, `None` if `alpha_threshold` is
+    /// unset or the image has no alpha channel.
+    fn resize_alpha(&self, img: &DynamicImage) -> Result<Option<GrayImage>, Error> {
+        if self.alpha_threshold.is_none() {
+            return Ok(None);
+        }
+        self.cell_alpha(img)
+    }
+
+    /// The alpha of every cell of `img`, averaged like the colors. `None` for sources without
+    /// an alpha channel.
+    pub(crate) fn cell_alpha(&self, img: &DynamicImage) -> Result<Option<GrayImage>, Error> {
+        if !img.color().has_alpha() {
+            return Ok(None);
+        }
+        let alpha: Vec<u8> = match img.as_rgba8() {
+            Some(rgba) => rgba.pixels().map(|pixel| pixel.0[3]).collect(),
+            None => img.to_rgba8().pixels().map(|pixel| pixel.0[3]).collect(),
+        };
+        let mut resized = Vec::new();
+        let (dst_w, dst_h) = self.resize_raw(
+            img,
+            &alpha,
+            1,
+            fir::PixelType::U8,
+            &mut fir::Resizer::new(),
+            &mut resized,
+        )?;
+        Ok(GrayImage::from_vec(dst_w, dst_h, resized))
+    }
+
+    /// The region of a `width` x `height` image that is converted, `crop` or else the borders
+    /// found by `detect_borders`, and the size it is resized to with `scale` x `scale` pixels
+    /// per cell.
+    fn output_size(&self, width: u32, height: u32, scale: u32) -> (Crop, u32, u32) {
+        let crop = match self.crop.or(self.border_crop) {
+            Some(crop) if width > 0 && height > 0 => crop.clamp(width, height),
+            _ => Crop {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            },
+        };
+        let (dst_w, dst_h) = self.resolution.calc_for(crop.width, crop.height);
+        // every pixel becomes one cell of `cell_width` terminal columns
+        let dst_w = (dst_w / self.cell_width() as u32).max(1) * scale;
+        (crop, dst_w, dst_h * scale)
+    }
+
+    /// Resizes `pixels`, the pixels of `img` as `pixel_type`, to `scale` x `scale` pixels per
+    /// cell of the region `crop` (or else the borders found by `detect_borders`) leaves, into
+    /// `dst`. Returns the size of the resized image.
+    fn resize_raw(
+        &self,
+        img: &DynamicImage,
+        pixels: &[u8],
+        scale: u32,
+        pixel_type: fir::PixelType,
+        resizer: &mut fir::Resizer,
+        dst: &mut Vec<u8>,
+    ) -> Result<(u32, u32), Error> {
+        let (width, height) = (img.width(), img.height());
+        let src_image = fir::images::ImageRef::new(width, height, pixels, pixel_type)
+            .map_err(|err| Error::Pipeline(format!("{ERROR_RESIZE}:{err:?}")))?;
+
+        // cropping happens inside the resizer instead of copying the region first
+        let (crop, dst_w, dst_h) = self.output_size(width, height, scale);
+        // keeps the allocation of `dst` when the size is the same as last time
+        dst.resize(dst_w as usize * dst_h as usize * pixel_type.size(), 0);
+        let mut dst_image = fir::images::Image::from_slice_u8(dst_w, dst_h, dst, pixel_type)
+            .map_err(|err| Error::Pipeline(format!("{ERROR_RESIZE}:{err:?}")))?;
+        // when downscaling every cell averages the source pixels it covers unless told to
+        // sample a single pixel, upscaling just repeats pixels
+        let downscaling = dst_w < crop.width || dst_h < crop.height;
+        let resize_alg = if downscaling && self.sample_point == SamplePoint::Area {
+            fir::ResizeAlg::Convolution(fir::FilterType::Box)
+        } else {
+            fir::ResizeAlg::Nearest
+        };
 
-        let mut resizer = fir::Resizer::new();
         resizer
             .resize(
                 &src_image,
                 &mut dst_image,
-                &fir::ResizeOptions::new().resize_alg(fir::ResizeAlg::Nearest),
+                &fir::ResizeOptions::new().resize_alg(resize_alg).crop(
+                    crop.x as f64,
+                    crop.y as f64,
+                    crop.width as f64,
+                    crop.height as f64,
+                ),
             )
             .map_err(|err| Error::Pipeline(format!("{ERROR_RESIZE}:{err:?}")))?;
 
-        let dst_image = dst_image.into_vec();
-        let img_buff = image::ImageBuffer::<image::Rgb<u8>, _>::from_vec(dst_w, dst_h, dst_image)
-            .ok_or(Error::Pipeline(ERROR_DATA.to_string()))?;
+        Ok((dst_w, dst_h))
+    }
 
-        Ok(DynamicImage::ImageRgb8(img_buff))
+    /// The char for `lum`, from the band containing it or else from the main char map.
+    fn lookup(&self, lum: u8) -> char {
+        let (char_map, low, high) = self
+            .bands
+            .iter()
+            .find(|band| band.range.contains(&lum) && !band.char_map.is_empty())
+            .map_or((&self.char_map, 0, u8::MAX), |band| {
+                (&band.char_map, *band.range.start(), *band.range.end())
+            });
+        let span = (high - low) as usize + 1;
+        let lookup_idx = char_map.len() * (lum - low) as usize / span;
+        char_map[lookup_idx.min(char_map.len() - 1)]
+    }
+
+    /// Converts `cells`, the luminance of `frame` as resized by `resize`, to chars like
+    /// `to_ascii`, but with the settings that need the source frame: transparent cells with
+    /// `alpha_threshold` and, in structural mode, glyphs matched against `frame` resized to
+    /// 2x2 pixels per cell.
+    pub fn cells_to_ascii(&self, frame: &DynamicImage, cells: &GrayImage) -> Result<String, Error> {
+        let alpha = self.resize_alpha(frame)?;
+        #[cfg(feature = "render")]
+        if let (RenderMode::Structural, Some(atlas)) = (self.mode, &self.glyph_atlas) {
+            let quadrants = self.resize_scaled(frame, 2)?.into_luma8();
+            return Ok(self.to_ascii_structural(&quadrants, atlas, alpha.as_ref()));
+        }
+        Ok(self.to_ascii_by(cells, alpha.as_ref(), |lum| self.lookup(lum)))
+    }
+
+    /// Whether the cell at (`x`, `y`) of `alpha` is below `alpha_threshold`.
+    fn is_transparent(&self, alpha: Option<&GrayImage>, x: u32, y: u32) -> bool {
+        match (alpha, self.alpha_threshold) {
+            (Some(alpha), Some(threshold)) => alpha
+                .get_pixel_checked(x, y)
+                .is_some_and(|a| a[0] < threshold),
+            _ => false,
+        }
+    }
+
+    /// Picks a glyph for every 2x2 block of `quadrants` by shape.
+    #[cfg(feature = "render")]
+    fn to_ascii_structural(
+        &self,
+        quadrants: &GrayImage,
+        atlas: &GlyphAtlas,
+        alpha: Option<&GrayImage>,
+    ) -> String {
+        let lum = |x, y| {
+            let lum = quadrants.get_pixel(x, y)[0];
+            self.luma_lut.map_or(lum, |lut| lut[lum as usize]) as f32 / u8::MAX as f32
+        };
+        self.layout(quadrants.width() / 2, quadrants.height() / 2, |x, y| {
+            if self.is_transparent(alpha, x, y) {
+                return self.transparent_char;
+            }
+            let (x, y) = (x * 2, y * 2);
+            let pattern = [lum(x, y), lum(x + 1, y), lum(x, y + 1), lum(x + 1, y + 1)];
+            atlas
+                .best_match(pattern)
+                .unwrap_or_else(|| self.lookup(u8::MAX))
+        })
     }
 
     pub fn to_ascii(&self, input: &GrayImage) -> String {
-        let (width, height) = (input.width(), input.height());
-        let capacity = (width + 1) * height + 1;
+        self.to_ascii_by(input, None, |lum| self.lookup(lum))
+    }
+
+    /// Like `to_ascii`, but as a grid of `input.width() * cell_width()` x `input.height()` chars,
+    /// wide glyphs followed by their padding, whether or not `new_lines` is set.
+    pub fn to_grid(&self, input: &GrayImage) -> Grid<char> {
+        let cells: Vec<char> = self
+            .to_ascii(input)
+            .chars()
+            .filter(|c| !matches!(c, '\r' | '\n'))
+            .collect();
+        Grid {
+            width: input.width() as usize * self.cell_width(),
+            height: if cells.is_empty() {
+                0
+            } else {
+                input.height() as usize
+            },
+            cells,
+        }
+    }
+
+    /// Like `to_ascii`, but `index_fn` maps the luminance (after levels, equalization and
+    /// detail) to an index into `char_map`, e.g. for custom tone curves or thresholds.
+    /// Indices past the end are clamped to the last char and luma bands are not used.
+    pub fn to_ascii_with(&self, input: &GrayImage, index_fn: impl Fn(u8) -> usize) -> String {
+        let last = self.char_map.len().saturating_sub(1);
+        self.to_ascii_by(input, None, |lum| self.char_map[index_fn(lum).min(last)])
+    }
+
+    /// Picks the char of every cell of `input`, cells transparent in `alpha` get
+    /// `transparent_char`.
+    fn to_ascii_by(
+        &self,
+        input: &GrayImage,
+        alpha: Option<&GrayImage>,
+        pick: impl Fn(u8) -> char,
+    ) -> String {
+        self.layout(input.width(), input.height(), |x, y| match self.mode {
+            RenderMode::Blocks => ' ',
+            _ if self.is_transparent(alpha, x, y) => self.transparent_char,
+            _ => {
+                let lum = input.get_pixel(x, y)[0];
+                let lum = self.luma_lut.map_or(lum, |lut| lut[lum as usize]);
+                let lum = if self.detail > 0.0 {
+                    let boost = self.detail * local_std_dev(input, x, y);
+                    (lum as f32 + boost).min(u8::MAX as f32) as u8
+                } else {
+                    lum
+                };
+                pick(lum)
+            }
+        })
+    }
+
+    /// Lays out the char of every cell of a `width` x `height` grid, padded to `cell_width`.
+    fn layout(&self, width: u32, height: u32, cell: impl Fn(u32, u32) -> char) -> String {
+        let cell_width = self.cell_width();
+        let capacity = (width * cell_width as u32 + 2) * height + 1;
         let mut output = String::with_capacity(capacity as usize);
 
+        if self.char_map.is_empty() {
+            return output;
+        }
+
         for y in 0..height {
-            output.extend((0..width).map(|x| {
-                let lum = input.get_pixel(x, y)[0] as u32;
-                let lookup_idx = self.char_map.len() * lum as usize / (u8::MAX as usize + 1);
-                self.char_map[lookup_idx]
-            }));
+            for x in 0..width {
+                let c = cell(x, y);
+                output.push(c);
+                for _ in display_width(c)..cell_width {
+                    output.push(' ');
+                }
+            }
 
             if self.new_lines && y < height - 1 {
                 output.push('\r');
@@ -92,3 +949,435 @@ impl ImagePipeline {
         output
     }
 }
+
+/// Standard deviation of the luminance in the 3x3 neighbourhood of (`x`, `y`).
+fn local_std_dev(input: &GrayImage, x: u32, y: u32) -> f32 {
+    let (mut sum, mut sum_sq, mut n) = (0_f32, 0_f32, 0_f32);
+    for ny in y.saturating_sub(1)..=(y + 1).min(input.height() - 1) {
+        for nx in x.saturating_sub(1)..=(x + 1).min(input.width() - 1) {
+            let lum = input.get_pixel(nx, ny)[0] as f32;
+            sum += lum;
+            sum_sq += lum * lum;
+            n += 1.0;
+        }
+    }
+    let mean = sum / n;
+    (sum_sq / n - mean * mean).max(0.0).sqrt()
+}
+
+/// The two tones of the checkerboard transparent pixels are composited over with `checker`.
+const CHECKER_COLORS: [[u8; 3]; 2] = [[153; 3], [102; 3]];
+
+/// Converts `img` to RGB, compositing transparent pixels over the `background` color of their
+/// position.
+fn flatten(img: &DynamicImage, background: impl Fn(u32, u32) -> [u8; 3]) -> RgbImage {
+    if !img.color().has_alpha() {
+        return img.to_rgb8();
+    }
+
+    let rgba = match img.as_rgba8() {
+        Some(rgba) => Cow::Borrowed(rgba),
+        None => Cow::Owned(img.to_rgba8()),
+    };
+    RgbImage::from_fn(img.width(), img.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let background = background(x, y);
+        let (a, inv) = (a as u32, 255 - a as u32);
+        let blend = |fg: u8, bg: u8| ((fg as u32 * a + bg as u32 * inv + 127) / 255) as u8;
+        Rgb([
+            blend(r, background[0]),
+            blend(g, background[1]),
+            blend(b, background[2]),
+        ])
+    })
+}
+
+/// Chainable builder for `ImagePipeline`, every setting not given falls back to a sensible default.
+pub struct ImagePipelineBuilder {
+    resolution: Resolution,
+    char_map: CharMap,
+    bands: Vec<(RangeInclusive<u8>, CharMap)>,
+    new_lines: bool,
+    equalization: Equalization,
+    auto_levels: AutoLevels,
+    mode: RenderMode,
+    crop: Option<Crop>,
+    autocrop: AutoCrop,
+    sample_point: SamplePoint,
+    detail: f32,
+    grayscale: bool,
+    palette: Option<Palette>,
+    palette_dither: bool,
+    colormap: Option<Colormap>,
+    alpha_threshold: Option<u8>,
+    transparent_char: char,
+    background: [u8; 3],
+    checker: bool,
+    tone_curve: Option<ToneCurve>,
+    bg_shade: Option<f32>,
+    stabilize: Option<u8>,
+    keep_alpha: bool,
+}
+
+impl Default for ImagePipelineBuilder {
+    fn default() -> Self {
+        Self {
+            resolution: Resolution::Fixed(80, 24),
+            char_map: CharMap::Dotted,
+            bands: Vec::new(),
+            new_lines: false,
+            equalization: Equalization::Off,
+            auto_levels: AutoLevels::Off,
+            mode: RenderMode::Chars,
+            crop: None,
+            autocrop: AutoCrop::Off,
+            sample_point: SamplePoint::Area,
+            detail: 0.0,
+            grayscale: false,
+            palette: None,
+            palette_dither: false,
+            colormap: None,
+            alpha_threshold: None,
+            transparent_char: ' ',
+            background: [0, 0, 0],
+            checker: false,
+            tone_curve: None,
+            bg_shade: None,
+            stabilize: None,
+            keep_alpha: false,
+        }
+    }
+}
+
+impl ImagePipelineBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the target resolution, defaults to 80x24.
+    pub fn resolution(mut self, resolution: Resolution) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    /// Sets the character lookup table, defaults to `CharMap::Dotted`.
+    pub fn char_map(mut self, char_map: CharMap) -> Self {
+        self.char_map = char_map;
+        self
+    }
+
+    /// Uses `char_map` for luminance within `range` instead of the main char map, can be called
+    /// several times. Overlapping ranges are rejected by `build`.
+    pub fn band(mut self, range: RangeInclusive<u8>, char_map: CharMap) -> Self {
+        self.bands.push((range, char_map));
+        self
+    }
+
+    /// Sets whether newlines are added at the end of each line, defaults to `false`.
+    pub fn new_lines(mut self, new_lines: bool) -> Self {
+        self.new_lines = new_lines;
+        self
+    }
+
+    /// Sets the histogram equalization mode, defaults to `Equalization::Off`.
+    pub fn equalization(mut self, equalization: Equalization) -> Self {
+        self.equalization = equalization;
+        self
+    }
+
+    /// Sets the automatic level stretch mode, defaults to `AutoLevels::Off`.
+    pub fn auto_levels(mut self, auto_levels: AutoLevels) -> Self {
+        self.auto_levels = auto_levels;
+        self
+    }
+
+    /// Sets what cells are made of, defaults to `RenderMode::Chars`.
+    pub fn mode(mut self, mode: RenderMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the region of the source to convert, defaults to the whole image.
+    pub fn crop(mut self, crop: Option<Crop>) -> Self {
+        self.crop = crop;
+        self
+    }
+
+    /// Sets the border detection mode, defaults to `AutoCrop::Off`.
+    pub fn autocrop(mut self, autocrop: AutoCrop) -> Self {
+        self.autocrop = autocrop;
+        self
+    }
+
+    /// Sets how cells sample the source when downscaling, defaults to `SamplePoint::Area`.
+    pub fn sample_point(mut self, sample_point: SamplePoint) -> Self {
+        self.sample_point = sample_point;
+        self
+    }
+
+    /// Sets how strongly detailed areas are pushed towards denser glyphs, defaults to `0.0` (off).
+    pub fn detail(mut self, detail: f32) -> Self {
+        self.detail = detail;
+        self
+    }
+
+    /// Sets whether frames are rendered without colors, defaults to `false`.
+    pub fn grayscale(mut self, grayscale: bool) -> Self {
+        self.grayscale = grayscale;
+        self
+    }
+
+    /// Sets the palette the output colors are snapped to, defaults to `None` (all colors).
+    pub fn palette(mut self, palette: Option<Palette>) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Sets whether colors are dithered when snapped to the palette, defaults to `false`.
+    pub fn palette_dither(mut self, palette_dither: bool) -> Self {
+        self.palette_dither = palette_dither;
+        self
+    }
+
+    /// Sets the alpha below which cells become the transparent char, defaults to `None`
+    /// (transparent pixels are composited over the background).
+    pub fn alpha_threshold(mut self, alpha_threshold: Option<u8>) -> Self {
+        self.alpha_threshold = alpha_threshold;
+        self
+    }
+
+    /// Sets the char transparent cells are drawn with, defaults to a space.
+    pub fn transparent_char(mut self, transparent_char: char) -> Self {
+        self.transparent_char = transparent_char;
+        self
+    }
+
+    /// Sets the colormap cells are colored with by their luminance, defaults to `None` (the
+    /// source colors).
+    pub fn colormap(mut self, colormap: Option<Colormap>) -> Self {
+        self.colormap = colormap;
+        self
+    }
+
+    /// Sets the color transparent sources are composited over, defaults to black.
+    pub fn background(mut self, background: [u8; 3]) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Sets whether transparent sources are composited over a checkerboard instead of the
+    /// background color, defaults to `false`.
+    pub fn checker(mut self, checker: bool) -> Self {
+        self.checker = checker;
+        self
+    }
+
+    /// Sets the tone curve the luminance is mapped through after the levels and equalization,
+    /// defaults to `None`.
+    pub fn tone_curve(mut self, tone_curve: Option<ToneCurve>) -> Self {
+        self.tone_curve = tone_curve;
+        self
+    }
+
+    /// Sets the factor the colors are darkened by for the cell backgrounds, defaults to `None`
+    /// (no backgrounds).
+    pub fn bg_shade(mut self, bg_shade: Option<f32>) -> Self {
+        self.bg_shade = bg_shade;
+        self
+    }
+
+    /// Sets how far the luminance of a cell has to move before its char changes, defaults to
+    /// `None` (every change shows).
+    pub fn stabilize(mut self, stabilize: Option<u8>) -> Self {
+        self.stabilize = stabilize;
+        self
+    }
+
+    /// Sets whether rendered frames keep the alpha of every cell, defaults to `false`.
+    pub fn keep_alpha(mut self, keep_alpha: bool) -> Self {
+        self.keep_alpha = keep_alpha;
+        self
+    }
+
+    /// Builds the pipeline, fails if a char map is empty or luma bands overlap.
+    pub fn build(self) -> Result<ImagePipeline, Error> {
+        let char_map = self.char_map.chars();
+        if char_map.is_empty() {
+            return Err(Error::Pipeline(ERROR_EMPTY_CHAR_MAP.to_string()));
+        }
+
+        let mut bands = Vec::with_capacity(self.bands.len());
+        for (range, band_map) in self.bands {
+            let band_map = band_map.chars();
+            if band_map.is_empty() || range.is_empty() {
+                return Err(Error::Pipeline(ERROR_EMPTY_CHAR_MAP.to_string()));
+            }
+            if let Some(other) = bands.iter().find(|other: &&LumaBand| {
+                range.start() <= other.range.end() && other.range.start() <= range.end()
+            }) {
+                return Err(Error::Pipeline(format!(
+                    "Luma band {range:?} overlaps {:?}",
+                    other.range
+                )));
+            }
+            bands.push(LumaBand {
+                range,
+                char_map: band_map,
+            });
+        }
+
+        #[cfg(feature = "render")]
+        let glyph_atlas = (self.mode == RenderMode::Structural).then(|| GlyphAtlas::new(&char_map));
+
+        Ok(ImagePipeline {
+            resolution: self.resolution,
+            char_map,
+            bands,
+            new_lines: self.new_lines,
+            equalization: self.equalization,
+            auto_levels: self.auto_levels,
+            mode: self.mode,
+            crop: self.crop,
+            autocrop: self.autocrop,
+            sample_point: self.sample_point,
+            detail: self.detail,
+            grayscale: self.grayscale,
+            palette: self.palette,
+            palette_dither: self.palette_dither,
+            colormap: self.colormap.map(|colormap| (colormap, colormap.lut())),
+            alpha_threshold: self.alpha_threshold,
+            transparent_char: self.transparent_char,
+            background: self.background,
+            checker: self.checker,
+            bg_shade: self.bg_shade,
+            stabilize: self.stabilize,
+            keep_alpha: self.keep_alpha,
+            levels_lut: None,
+            levels_window: VecDeque::new(),
+            equalization_lut: None,
+            luma_lut: self.tone_curve.as_ref().map(|curve| *curve.lut()),
+            tone_curve: self.tone_curve,
+            border_crop: None,
+            border_frames: 0,
+            resize_cache: (fir::Resizer::new(), RgbImage::default()),
+            stable_luma: None,
+            #[cfg(feature = "render")]
+            glyph_atlas,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolutions_are_at_least_one_cell() {
+        assert_eq!(Resolution::Fixed(0, 0).calc_for(640, 480), (1, 1));
+        assert_eq!(Resolution::Fixed(0, 3).calc_for(640, 480), (1, 3));
+        // a divisor larger than the image
+        assert_eq!(Resolution::Divisor(1000).calc_for(640, 480), (1, 1));
+        assert_eq!(Resolution::Divisor(500).calc_for(640, 480), (1, 1));
+        assert_eq!(Resolution::Divisor(0).calc_for(4, 2), (4, 2));
+        assert_eq!(Resolution::FitWidth(0).calc_for(0, 0), (1, 1));
+
+        let tiny = DynamicImage::ImageRgb8(RgbImage::new(3, 3));
+        assert_eq!(Resolution::Divisor(8).calc(&tiny), (1, 1));
+        let pipeline = ImagePipeline::new(Resolution::Fixed(0, 0), CharMap::Chars1, true).unwrap();
+        let resized = pipeline.resize(&tiny).unwrap();
+        assert_eq!((resized.width(), resized.height()), (1, 1));
+    }
+
+    /// A `size` x `size` checkerboard of single black and white pixels.
+    fn checkerboard(size: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(size, size, |x, y| {
+            Rgb([if (x + y) % 2 == 0 { 0 } else { u8::MAX }; 3])
+        }))
+    }
+
+    /// Mean luminance of `img`.
+    fn mean_luma(img: &DynamicImage) -> f64 {
+        let luma = img.to_luma8();
+        luma.pixels().map(|p| p[0] as f64).sum::<f64>() / luma.len() as f64
+    }
+
+    #[test]
+    fn area_sampling_averages_downscaled_cells() {
+        let source = checkerboard(64);
+        assert!((mean_luma(&source) - 127.5).abs() < 1.0);
+
+        let mut pipeline =
+            ImagePipeline::new(Resolution::Fixed(8, 8), CharMap::Chars1, true).unwrap();
+        let area = pipeline.resize(&source).unwrap();
+        assert_eq!((area.width(), area.height()), (8, 8));
+        assert!((mean_luma(&area) - 127.0).abs() < 2.0);
+        // every cell covers as many black as white pixels, so none is black or white
+        assert!(
+            area.to_luma8()
+                .pixels()
+                .all(|p| (120..=135).contains(&p[0]))
+        );
+
+        pipeline.sample_point = SamplePoint::Center;
+        let center = pipeline.resize(&source).unwrap();
+        assert!(
+            center
+                .to_luma8()
+                .pixels()
+                .all(|p| matches!(p[0], 0 | u8::MAX))
+        );
+    }
+
+    #[test]
+    fn grayscale_resizes_the_luminance_like_the_colors() {
+        let source = DynamicImage::ImageRgb8(RgbImage::from_fn(64, 64, |x, y| {
+            Rgb([x as u8 * 4, y as u8 * 4, (x + y) as u8 * 2])
+        }));
+        let mut pipeline =
+            ImagePipeline::new(Resolution::Fixed(8, 8), CharMap::Chars1, true).unwrap();
+        let colored = pipeline.resize_cached(&source).unwrap();
+        pipeline.set_grayscale(true);
+        let gray = pipeline.resize_cached(&source).unwrap();
+
+        assert_eq!(gray.dimensions(), colored.dimensions());
+        // both average the same pixels, only rounding may differ
+        assert!(
+            gray.pixels()
+                .zip(colored.pixels())
+                .all(|(a, b)| a[0].abs_diff(b[0]) <= 1)
+        );
+    }
+
+    #[test]
+    fn empty_char_maps_are_rejected() {
+        assert!(matches!(CharMap::custom(""), Err(Error::Pipeline(_))));
+        assert!(matches!(CharMap::custom(" \t\n"), Err(Error::Pipeline(_))));
+
+        let pipeline =
+            ImagePipeline::new(Resolution::Fixed(4, 2), CharMap::Custom(Vec::new()), true);
+        assert!(matches!(pipeline, Err(Error::Pipeline(_))));
+
+        let banded = ImagePipeline::builder()
+            .band(0..=127, CharMap::Custom(Vec::new()))
+            .build();
+        assert!(matches!(banded, Err(Error::Pipeline(_))));
+    }
+
+    #[test]
+    fn empty_char_map_converts_to_nothing() {
+        let mut pipeline = ImagePipeline::new(
+            Resolution::Fixed(4, 2),
+            CharMap::custom(" #").unwrap(),
+            true,
+        )
+        .unwrap();
+        pipeline.set_char_map(CharMap::Custom(Vec::new()));
+        assert_eq!(pipeline.char_map, vec![' ', '#']);
+
+        pipeline.char_map.clear();
+        let cells = GrayImage::from_pixel(4, 2, image::Luma([200]));
+        assert_eq!(pipeline.to_ascii(&cells), "");
+        assert_eq!(pipeline.to_ascii_with(&cells, |_| 1), "");
+        assert!(pipeline.to_grid(&cells).cells.is_empty());
+    }
+}
@@ -1,88 +1,151 @@
-use std::time::Duration;
+use std::{
+    io,
+    ops::{Bound, ControlFlow, RangeBounds},
+    time::{Duration, Instant},
+};
 
 use image::DynamicImage;
 
-use crate::{error::Error, frames::FrameIterator, pipeline::ImagePipeline};
+use crate::{
+    clock::{Clock, SystemClock},
+    color::{self, ColorDepth},
+    error::Error,
+    frames::FrameIterator,
+    maps::display_width,
+    pipeline::{Grid, ImagePipeline, RenderMode},
+};
 
+/// A rendered frame, kept as rows of characters with one color per character.
+#[derive(Debug, Clone, Default)]
 pub struct RenderFrame {
-    pub text: String,
-    pub colors: Vec<u8>,
+    /// The rows of the frame without line breaks, a single row unless the pipeline adds new lines.
+    pub lines: Vec<String>,
+    /// The color of every character in `lines`, row after row. Empty when the pipeline is
+    /// grayscale (unless it fills blocks).
+    pub colors: Vec<[u8; 3]>,
+    /// Whether the colors fill the cell backgrounds (`RenderMode::Blocks`) instead of the glyphs.
+    pub fill: bool,
+    /// The background color of every character, laid out like `colors`. Empty unless the
+    /// pipeline shades the backgrounds (see `ImagePipeline::bg_shade`), leaving the background
+    /// to the terminal.
+    pub bg_colors: Vec<[u8; 3]>,
+    /// The alpha of every character, laid out like `colors`. Empty unless the pipeline keeps
+    /// the alpha (see `ImagePipeline::keep_alpha`) of a source that has one.
+    pub alphas: Vec<u8>,
+}
+
+/// Playback statistics collected by `Renderer::run`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderStats {
+    /// Frames rendered so far.
+    pub frames_rendered: u64,
+    /// Frames skipped to keep up with the target fps.
+    pub frames_skipped: u64,
+    /// Render rate measured over roughly the last second.
+    pub render_fps: f64,
+    /// The target (source) frames per second.
+    pub source_fps: f64,
+    /// Output resolution (width, height) of the last rendered frame.
+    pub resolution: (u32, u32),
+    /// Total time spent decoding media frames.
+    pub decode_time: Duration,
+    /// Total time spent resizing frames (including border detection).
+    pub resize_time: Duration,
+    /// Total time spent converting resized frames to chars and colors.
+    pub convert_time: Duration,
 }
 
 pub struct CallbackState<'a> {
     pub frame: Option<RenderFrame>,
     pub should_render: bool,
     pub pipeline: &'a mut ImagePipeline,
+    /// Time left until the next frame is due, callbacks can block (e.g. poll input) for up to this long.
+    pub time_until_next_frame: Duration,
+    /// Playback statistics, e.g. for a debug overlay.
+    pub stats: RenderStats,
 }
 
 #[cfg(feature = "render")]
 impl RenderFrame {
+    /// Draws the frame with the bundled font at `font_px` over `background_color`. Frames with
+    /// alpha are drawn over a transparent background instead, with the alpha of every char.
+    /// Frames without colors are drawn in black or white, whichever stands out from the
+    /// background.
     pub fn render_to_image(&self, font_px: f32, background_color: &[u8; 4]) -> image::RgbaImage {
+        use image::{Pixel, Rgba};
         use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
-        let height = self.text.lines().count();
+        let rows: Vec<_> = self
+            .rows()
+            .zip(self.bg_rows())
+            .zip(self.alpha_rows())
+            .collect();
+        let background_color = if self.alphas.is_empty() {
+            *background_color
+        } else {
+            [0; 4]
+        };
+        let [r, g, b, _] = background_color;
+        let foreground = if image::Rgb([r, g, b]).to_luma()[0] > 127 {
+            [0; 3]
+        } else {
+            [u8::MAX; 3]
+        };
 
         let img_width = (self
-            .text
-            .lines()
-            .next()
+            .lines
+            .first()
             .map(|line| line.chars().count())
             .unwrap_or(0) as f32
             * font_px)
             .ceil() as u32;
 
-        let img_height = (height as f32 * font_px).ceil() as u32;
+        let img_height = (rows.len() as f32 * font_px).ceil() as u32;
 
-        let font_data = include_bytes!("JetBrainsMono-Regular.ttf");
-        let font = ab_glyph::FontRef::try_from_slice(font_data.as_slice()).unwrap();
+        let font = ab_glyph::FontRef::try_from_slice(crate::glyphs::FONT_DATA)
+            .expect("bundled font is valid");
 
-        let mut color_idx = 0;
-        let lines_data: Vec<(String, &[u8])> = self
-            .text
-            .lines()
-            .map(|line| {
-                let line_len = line.chars().count();
-                let color_slice = &self.colors[color_idx..color_idx + 3 * line_len];
-                color_idx += 3 * line_len + 6;
-                (line.to_string(), color_slice)
-            })
-            .collect();
-
-        let line_images: Vec<image::RgbaImage> = lines_data
+        let line_images: Vec<image::RgbaImage> = rows
             .par_iter()
-            .map(|(line, colors)| {
-                use image::{Pixel, Rgba};
-
+            .map(|(((line, colors), bg_colors), alphas)| {
                 let line_width = (line.chars().count() as f32 * font_px).ceil() as u32;
                 let line_height = font_px.ceil() as u32;
 
                 let mut img = image::RgbaImage::from_pixel(
                     line_width,
                     line_height,
-                    *Rgba::<u8>::from_slice(background_color),
+                    *Rgba::<u8>::from_slice(&background_color),
                 );
 
-                let mut local_color_idx = 0;
                 for (x, ch) in line.chars().enumerate() {
-                    if local_color_idx + 2 >= colors.len() {
-                        break;
+                    let rgb = colors.get(x).unwrap_or(&foreground);
+                    let alpha = alphas.get(x).copied().unwrap_or(u8::MAX);
+                    if let Some(bg) = bg_colors.get(x) {
+                        imageproc::drawing::draw_filled_rect_mut(
+                            &mut img,
+                            imageproc::rect::Rect::at((x as f32 * font_px) as i32, 0)
+                                .of_size(font_px.ceil() as u32, line_height),
+                            image::Rgba([bg[0], bg[1], bg[2], alpha]),
+                        );
+                    }
+                    if self.fill {
+                        imageproc::drawing::draw_filled_rect_mut(
+                            &mut img,
+                            imageproc::rect::Rect::at((x as f32 * font_px) as i32, 0)
+                                .of_size(font_px.ceil() as u32, line_height),
+                            image::Rgba([rgb[0], rgb[1], rgb[2], alpha]),
+                        );
+                        continue;
                     }
-                    let color = image::Rgba([
-                        colors[local_color_idx],
-                        colors[local_color_idx + 1],
-                        colors[local_color_idx + 2],
-                        255,
-                    ]);
                     imageproc::drawing::draw_text_mut(
                         &mut img,
-                        color,
+                        image::Rgba([rgb[0], rgb[1], rgb[2], alpha]),
                         (x as f32 * font_px) as i32,
                         0,
                         font_px,
                         &font,
                         &ch.to_string(),
                     );
-                    local_color_idx += 3;
                 }
                 img
             })
@@ -100,15 +163,270 @@ impl RenderFrame {
     }
 }
 
+impl RenderFrame {
+    /// Runs `frame` through `pipeline` once, without any playback timing.
+    pub fn from_image(pipeline: &mut ImagePipeline, frame: &DynamicImage) -> Result<Self, Error> {
+        render_image(pipeline, frame).map(|(render_frame, ..)| render_frame)
+    }
+
+    /// Iterates over the rows together with the colors of their characters.
+    pub fn rows(&self) -> impl Iterator<Item = (&str, &[[u8; 3]])> {
+        self.lines
+            .iter()
+            .map(String::as_str)
+            .zip(self.split_rows(&self.colors))
+    }
+
+    /// The background colors of every row, like the colors of `rows`. The rows are empty when
+    /// the frame has no background colors.
+    pub fn bg_rows(&self) -> impl Iterator<Item = &[[u8; 3]]> {
+        self.split_rows(&self.bg_colors)
+    }
+
+    /// The alpha of every row, like the colors of `rows`. The rows are empty when the frame
+    /// has no alpha.
+    pub fn alpha_rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.split_rows(&self.alphas)
+    }
+
+    /// Splits `values`, one per char, into the rows of the frame.
+    fn split_rows<'a, T>(&'a self, values: &'a [T]) -> impl Iterator<Item = &'a [T]> {
+        let mut offset = 0;
+        self.lines.iter().map(move |line| {
+            let len = line.chars().count();
+            let row = values.get(offset..offset + len).unwrap_or_default();
+            offset += len;
+            row
+        })
+    }
+
+    /// The chars as a grid with a row per line. Frames rendered without `new_lines` are a
+    /// single line and so a grid of one row.
+    pub fn char_grid(&self) -> Grid<char> {
+        Grid {
+            width: self.grid_width(),
+            height: self.lines.len(),
+            cells: self.lines.iter().flat_map(|line| line.chars()).collect(),
+        }
+    }
+
+    /// The colors laid out like `char_grid`.
+    pub fn color_grid(&self) -> Grid<[u8; 3]> {
+        Grid {
+            width: self.grid_width(),
+            height: self.lines.len(),
+            cells: self.colors.clone(),
+        }
+    }
+
+    fn grid_width(&self) -> usize {
+        self.lines.first().map_or(0, |line| line.chars().count())
+    }
+
+    /// Dithers the colors against the xterm 256-color palette (see `color::dither_ansi256`),
+    /// for output with `ColorDepth::Ansi256`.
+    pub fn dither_ansi256(&mut self) {
+        let width = self.grid_width();
+        color::dither_ansi256(&mut self.colors, width);
+    }
+
+    /// Dithers the colors against the 16 standard colors (see `color::dither_ansi16`), for
+    /// output with `ColorDepth::Ansi16`.
+    pub fn dither_ansi16(&mut self) {
+        let width = self.grid_width();
+        color::dither_ansi16(&mut self.colors, width);
+    }
+
+    /// The rows joined with `\r\n`, as returned by `ImagePipeline::to_ascii`.
+    pub fn text(&self) -> String {
+        self.lines.join("\r\n")
+    }
+
+    /// The frame with truecolor SGR sequences, rows separated by `\n`. Every row ends with a
+    /// color reset, so the string can be printed as is.
+    pub fn to_ansi_string(&self) -> String {
+        self.ansi_string(ColorDepth::TrueColor)
+    }
+
+    /// Writes the frame as `to_ansi_string` does, with colors encoded at `depth`, to any sink
+    /// (a pipe, a socket, a `Vec<u8>`) without going through a terminal player. Rows are
+    /// written one at a time instead of building the whole frame first, `out` is not flushed.
+    pub fn write_ansi(&self, out: &mut impl io::Write, depth: ColorDepth) -> io::Result<()> {
+        let mut row_buffer = String::new();
+        for (row, ((line, colors), bg_colors)) in self.rows().zip(self.bg_rows()).enumerate() {
+            row_buffer.clear();
+            if row > 0 {
+                row_buffer.push('\n');
+            }
+            push_ansi_chars(&mut row_buffer, line, colors, bg_colors, self.fill, depth);
+            out.write_all(row_buffer.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Writes the plain chars of the frame, rows separated by `\n`, to any sink. `out` is not
+    /// flushed.
+    pub fn write_text(&self, out: &mut impl io::Write) -> io::Result<()> {
+        for (row, line) in self.lines.iter().enumerate() {
+            if row > 0 {
+                out.write_all(b"\n")?;
+            }
+            out.write_all(line.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn ansi_string(&self, depth: ColorDepth) -> String {
+        // rough guess, neighbouring chars often share a color
+        let mut out = String::with_capacity(self.colors.len() * 8 + self.lines.len() * 6);
+        for (row, ((line, colors), bg_colors)) in self.rows().zip(self.bg_rows()).enumerate() {
+            if row > 0 {
+                out.push('\n');
+            }
+            push_ansi_chars(&mut out, line, colors, bg_colors, self.fill, depth);
+        }
+        out
+    }
+
+    /// Appends row `row` to `out` as `to_ansi_string` writes it, with colors encoded at
+    /// `depth`. Only the chars in `columns` are written, e.g. the part of a row shown in a
+    /// narrower viewport. Rows past the end of the frame append nothing.
+    pub fn push_ansi_row(
+        &self,
+        out: &mut String,
+        row: usize,
+        columns: impl RangeBounds<usize>,
+        depth: ColorDepth,
+    ) {
+        let Some((line, colors, bg_colors)) = self.row(row) else {
+            return;
+        };
+        let start = match columns.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match columns.end_bound() {
+            Bound::Included(&end) => end.saturating_add(1),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => usize::MAX,
+        }
+        .max(start);
+        let byte_at = |char_index: usize| {
+            line.char_indices()
+                .nth(char_index)
+                .map_or(line.len(), |(byte, _)| byte)
+        };
+        let colors = &colors[start.min(colors.len())..end.min(colors.len())];
+        let bg_colors = &bg_colors[start.min(bg_colors.len())..end.min(bg_colors.len())];
+        push_ansi_chars(
+            out,
+            &line[byte_at(start)..byte_at(end)],
+            colors,
+            bg_colors,
+            self.fill,
+            depth,
+        );
+    }
+
+    /// The chars of row `row` with their colors and background colors, like `rows` and
+    /// `bg_rows` but without walking the rows before it.
+    fn row(&self, row: usize) -> Option<(&str, &[[u8; 3]], &[[u8; 3]])> {
+        let line = self.lines.get(row)?;
+        // every row is as wide as the grid
+        let offset = row * self.grid_width();
+        let len = line.chars().count();
+        Some((
+            line,
+            self.colors.get(offset..offset + len).unwrap_or_default(),
+            self.bg_colors.get(offset..offset + len).unwrap_or_default(),
+        ))
+    }
+
+    /// The colors in the flat layout matching `text()`: 3 bytes per character and 6 zero bytes
+    /// for every `\r\n`.
+    pub fn flat_colors(&self) -> Vec<u8> {
+        let line_breaks = self.lines.len().saturating_sub(1);
+        let mut flat = Vec::with_capacity(3 * self.colors.len() + 6 * line_breaks);
+        for (row, (_, colors)) in self.rows().enumerate() {
+            if row > 0 {
+                flat.extend_from_slice(&[0; 6]);
+            }
+            flat.extend(colors.iter().flatten());
+        }
+        flat
+    }
+}
+
+/// Builds a frame from `text()` and `flat_colors()` style data.
 impl From<(String, Vec<u8>)> for RenderFrame {
-    fn from(val: (String, Vec<u8>)) -> Self {
+    fn from((text, flat_colors): (String, Vec<u8>)) -> Self {
+        let lines: Vec<String> = text.split("\r\n").map(String::from).collect();
+        let mut colors = Vec::with_capacity(flat_colors.len() / 3);
+        let mut offset = 0;
+        for line in &lines {
+            let len = 3 * line.chars().count();
+            let line_colors = flat_colors.get(offset..offset + len).unwrap_or_default();
+            colors.extend(
+                line_colors
+                    .chunks_exact(3)
+                    .map(|rgb| [rgb[0], rgb[1], rgb[2]]),
+            );
+            // skip the padding of the line break
+            offset += len + 6;
+        }
         RenderFrame {
-            text: val.0,
-            colors: val.1,
+            lines,
+            colors,
+            fill: false,
+            bg_colors: Vec::new(),
+            alphas: Vec::new(),
+        }
+    }
+}
+
+/// Appends `line` to `out` with an SGR sequence whenever the color changes between characters,
+/// followed by a color reset. `fill` frames color the background, others the foreground and the
+/// background with `bg_colors` unless it is empty. Rows without colors (grayscale frames) keep
+/// the terminal's colors.
+fn push_ansi_chars(
+    out: &mut String,
+    line: &str,
+    colors: &[[u8; 3]],
+    bg_colors: &[[u8; 3]],
+    fill: bool,
+    depth: ColorDepth,
+) {
+    if depth == ColorDepth::None || colors.is_empty() {
+        out.push_str(line);
+        return;
+    }
+
+    let shaded = !fill && !bg_colors.is_empty();
+    let (mut last_rgb, mut last_bg) = (None, None);
+    for (i, (c, &rgb)) in line.chars().zip(colors).enumerate() {
+        if last_rgb != Some(rgb) {
+            depth.push_sgr(out, rgb, fill);
+            last_rgb = Some(rgb);
         }
+        if shaded
+            && let Some(&bg) = bg_colors.get(i)
+            && last_bg != Some(bg)
+        {
+            depth.push_sgr(out, bg, true);
+            last_bg = Some(bg);
+        }
+        out.push(c);
+    }
+    depth.push_reset(out, fill);
+    if last_bg.is_some() {
+        depth.push_reset(out, true);
     }
 }
 
+/// Postprocessing hook run on source frames, see `Renderer::set_frame_transform`.
+pub type FrameTransform = Box<dyn Fn(&mut DynamicImage)>;
+
 pub struct Renderer {
     /// The image pipeline responsible for processing images.
     pub pipeline: ImagePipeline,
@@ -118,15 +436,49 @@ pub struct Renderer {
     last_frame: Option<DynamicImage>,
     /// Render options
     render_options: RenderOptions,
+    /// Number of frames consumed (rendered or skipped) since `start`.
+    frames_consumed: u64,
+    /// Playback statistics.
+    stats: RenderStats,
+    /// Time source for pacing the frames.
+    clock: Box<dyn Clock>,
+    /// Postprocessing applied to every frame before it is converted.
+    frame_transform: Option<FrameTransform>,
+}
+
+/// What happens to the frames skipped when rendering falls behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameSkipMode {
+    /// Drop them, the cheapest option.
+    #[default]
+    Drop,
+    /// Average them into the rendered frame, smoother for fast motion but every skipped frame
+    /// is still decoded.
+    Blend,
 }
 
 pub struct RenderOptions {
-    /// The target frames per second (frame rate) for the Renderer.
+    /// The target frames per second (frame rate) for the Renderer, a non-positive (or NaN)
+    /// fps renders as fast as possible.
     pub fps: f64,
     /// The width modifier (use 2 for emojis).
     pub w_mod: u32,
     /// loop back to the first frame after iterating through frames.
     pub loop_playback: bool,
+    /// Media position to start playback at.
+    pub start: Option<Duration>,
+    /// Media position to stop (or loop back to `start`) at.
+    pub end: Option<Duration>,
+    /// Stop after this many frames have been rendered.
+    pub max_frames: Option<usize>,
+    /// Show animated images with per-frame timing for their own delays instead of `fps`.
+    pub frame_delays: bool,
+    /// How frames are skipped when `run` is allowed to skip.
+    pub frame_skip_mode: FrameSkipMode,
+    /// Render at most this many frames per second, only ever lowers the rate given by the
+    /// source. With frame skipping the frames in between are skipped to stay in time,
+    /// otherwise playback slows down.
+    pub fps_cap: Option<f64>,
 }
 
 impl Renderer {
@@ -135,12 +487,47 @@ impl Renderer {
         media: FrameIterator,
         render_options: RenderOptions,
     ) -> Self {
-        Self {
+        let mut renderer = Self {
             pipeline,
             media,
             last_frame: None,
             render_options,
-        }
+            frames_consumed: 0,
+            stats: RenderStats::default(),
+            clock: Box::new(SystemClock),
+            frame_transform: None,
+        };
+        renderer.seek_to_start();
+        renderer
+    }
+
+    /// Jumps to `position` of the media before playback, without changing where looping
+    /// restarts (`start`). `end` still refers to the media position.
+    pub fn seek(&mut self, position: Duration) {
+        self.media.seek(position, self.render_options.fps);
+        let frame_secs = self.fps_frame_secs();
+        let since_start = position.saturating_sub(self.render_options.start.unwrap_or_default());
+        self.frames_consumed = if frame_secs > 0.0 {
+            (since_start.as_secs_f64() / frame_secs) as u64
+        } else {
+            0
+        };
+    }
+
+    /// Replaces the time source used to pace the frames, `SystemClock` by default.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) -> &mut Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Runs `transform` on every frame during `run` before it is resized and converted, e.g. to
+    /// overlay a watermark or timestamp. The transform gets the full-resolution source frame.
+    pub fn set_frame_transform(
+        &mut self,
+        transform: impl Fn(&mut DynamicImage) + 'static,
+    ) -> &mut Self {
+        self.frame_transform = Some(Box::new(transform));
+        self
     }
 
     pub fn run(
@@ -148,41 +535,156 @@ impl Renderer {
         allow_frame_skip: bool,
         callback: impl Fn(CallbackState) -> bool,
     ) -> Result<(), Error> {
-        let mut time_count = std::time::Instant::now();
+        self.run_until(None, allow_frame_skip, callback)
+    }
+
+    /// Like `run`, but also stops once `duration` has passed on the renderer's clock, e.g. for
+    /// demos or displays that refresh periodically. Unlike `max_frames` and `end` this bounds
+    /// the wall-clock time, however many frames that plays.
+    pub fn run_for(
+        &mut self,
+        duration: Duration,
+        allow_frame_skip: bool,
+        callback: impl Fn(CallbackState) -> bool,
+    ) -> Result<(), Error> {
+        let deadline = self.clock.now() + duration;
+        self.run_until(Some(deadline), allow_frame_skip, callback)
+    }
+
+    fn run_until(
+        &mut self,
+        deadline: Option<Instant>,
+        allow_frame_skip: bool,
+        callback: impl Fn(CallbackState) -> bool,
+    ) -> Result<(), Error> {
+        let mut schedule = FrameSchedule::new(self.clock.now());
         let mut should_continue = true;
+        self.stats.source_fps = self.render_options.fps;
+        // frames rendered since `fps_window_start`, to measure the render fps
+        let mut fps_window_start = self.clock.now();
+        let mut fps_window_frames = 0;
 
         while should_continue {
-            let (should_process_frame, frames_to_skip) = self.should_process_frame(&mut time_count);
+            if deadline.is_some_and(|deadline| self.clock.now() >= deadline) {
+                break;
+            }
+            let (should_process_frame, frames_to_skip) = self.should_process_frame(&mut schedule);
             let frame = if should_process_frame {
-                if allow_frame_skip && frames_to_skip > 0 {
-                    self.media.skip_frames(frames_to_skip);
+                if self.max_frames_reached() {
+                    break;
                 }
-                let f = self.get_current_frame();
-
-                if self.render_options.loop_playback && f.is_none() {
-                    // make sure the first frame on replay is shown immediately
-                    time_count -= self.target_frame_duration();
-                    // replay
-                    self.replay_pipeline();
+                let ControlFlow::Continue(f) =
+                    self.next_media_frame(allow_frame_skip, frames_to_skip)
+                else {
+                    break;
+                };
+                let frame = self.render_current_frame(f.as_ref());
+                // only frames that were actually produced count
+                if frame.is_some() {
+                    self.stats.frames_rendered += 1;
+                    fps_window_frames += 1;
                 }
-
-                self.render_current_frame(f.as_ref())
+                let fps_window = self.clock.elapsed_since(fps_window_start);
+                if fps_window >= Duration::from_secs(1) {
+                    self.stats.render_fps = fps_window_frames as f64 / fps_window.as_secs_f64();
+                    fps_window_start = self.clock.now();
+                    fps_window_frames = 0;
+                }
+                frame
             } else {
                 None
             };
 
+            let time_until_next_frame = self
+                .target_frame_duration()
+                .saturating_sub(self.clock.elapsed_since(schedule.due()));
+
             should_continue = callback(CallbackState {
                 frame,
                 should_render: should_process_frame,
                 pipeline: &mut self.pipeline,
+                time_until_next_frame,
+                stats: self.stats,
             });
         }
 
         Ok(())
     }
 
-    fn should_process_frame(&self, time_count: &mut std::time::Instant) -> (bool, usize) {
-        let (time_to_send_next_frame, frames_to_skip) = self.time_to_send_next_frame(time_count);
+    /// Renders the frames one by one as they are pulled, instead of pushing them to a callback
+    /// like `run`. The frames are untimed, every frame is rendered as soon as `next` is called
+    /// and none are skipped, which suits exports and tests. Stops after the last frame, at `end`
+    /// or after `max_frames`, `loop_playback` keeps it going.
+    pub fn frames(self) -> Frames {
+        Frames::new(self, None, false)
+    }
+
+    /// Like `frames`, but timed like `run`: `next` sleeps until the frame is due and skips
+    /// the frames it fell behind on when `allow_frame_skip` is set. Sleeps for real, so the
+    /// clock set with `set_clock` should follow the system time.
+    pub fn timed_frames(self, allow_frame_skip: bool) -> Frames {
+        let schedule = FrameSchedule::new(self.clock.now());
+        Frames::new(self, Some(schedule), allow_frame_skip)
+    }
+
+    /// Takes the next frame to render from the media, after skipping (or blending)
+    /// `frames_to_skip` frames when allowed. Breaks once playback is past `end` without
+    /// looping, a `None` frame means the media ended.
+    fn next_media_frame(
+        &mut self,
+        allow_frame_skip: bool,
+        frames_to_skip: usize,
+    ) -> ControlFlow<(), Option<DynamicImage>> {
+        if self.past_end() {
+            if !self.render_options.loop_playback {
+                return ControlFlow::Break(());
+            }
+            self.replay_pipeline();
+        }
+        let mut skipped = Vec::new();
+        if allow_frame_skip && frames_to_skip > 0 {
+            match self.render_options.frame_skip_mode {
+                // frames with their own delays are skipped by the time playback is behind,
+                // which is `frames_to_skip` of the current frame's duration
+                FrameSkipMode::Drop
+                    if self.render_options.frame_delays
+                        && matches!(self.media, FrameIterator::AnimatedImage { .. }) =>
+                {
+                    let behind = frames_to_skip as f64 * self.target_frame_secs();
+                    self.media
+                        .skip_duration(secs_to_duration(behind), self.render_options.fps);
+                }
+                FrameSkipMode::Drop => self.media.skip_frames(frames_to_skip),
+                FrameSkipMode::Blend => {
+                    skipped = self.media.by_ref().take(frames_to_skip).collect();
+                }
+            }
+            self.frames_consumed += frames_to_skip as u64;
+            self.stats.frames_skipped += frames_to_skip as u64;
+        }
+        let mut f = match self.get_current_frame() {
+            Some(frame) if !skipped.is_empty() => Some(blend_frames(&skipped, frame)),
+            f => f,
+        };
+
+        if self.render_options.loop_playback && f.is_none() {
+            // the last frame has been shown for its own duration, so the first frame
+            // is due now and its own delay schedules the frame after it
+            self.replay_pipeline();
+            f = self.get_current_frame();
+        }
+        self.frames_consumed += 1;
+        ControlFlow::Continue(f)
+    }
+
+    fn max_frames_reached(&self) -> bool {
+        self.render_options
+            .max_frames
+            .is_some_and(|max| self.stats.frames_rendered >= max as u64)
+    }
+
+    fn should_process_frame(&self, schedule: &mut FrameSchedule) -> (bool, usize) {
+        let (time_to_send_next_frame, frames_to_skip) = self.time_to_send_next_frame(schedule);
 
         if time_to_send_next_frame {
             (true, frames_to_skip)
@@ -193,73 +695,740 @@ impl Renderer {
 
     fn replay_pipeline(&mut self) {
         self.media.reset();
+        self.seek_to_start();
     }
 
-    fn time_to_send_next_frame(&self, time_count: &mut std::time::Instant) -> (bool, usize) {
-        let elapsed_time = time_count.elapsed();
-        let target_frame_duration = self.target_frame_duration();
+    fn seek_to_start(&mut self) {
+        if let Some(start) = self.render_options.start {
+            self.media.seek(start, self.render_options.fps);
+        }
+        self.frames_consumed = 0;
+    }
 
-        if elapsed_time >= target_frame_duration {
-            let frames_to_skip =
-                (elapsed_time.as_nanos() / target_frame_duration.as_nanos()) as usize - 1;
-            *time_count += target_frame_duration * (frames_to_skip as u32 + 1);
+    /// The current media position, derived from the consumed frames.
+    fn position(&self) -> Duration {
+        let consumed = self.frames_consumed as f64 * self.fps_frame_secs();
+        self.render_options
+            .start
+            .unwrap_or_default()
+            .saturating_add(secs_to_duration(consumed))
+    }
+
+    fn past_end(&self) -> bool {
+        self.render_options
+            .end
+            .is_some_and(|end| self.position() >= end)
+    }
+
+    fn time_to_send_next_frame(&self, schedule: &mut FrameSchedule) -> (bool, usize) {
+        let elapsed_time = self.clock.elapsed_since(schedule.due()).as_secs_f64();
+        let target_frame_secs = self.target_frame_secs();
+
+        if elapsed_time >= self.render_interval_secs() {
+            // nothing is ever late without a frame duration
+            let frames_to_skip = if target_frame_secs > 0.0 {
+                (elapsed_time / target_frame_secs) as usize - 1
+            } else {
+                0
+            };
+            if elapsed_time - target_frame_secs > MAX_CATCH_UP_SECS {
+                // the clock jumped (e.g. after system sleep), skip a bounded amount and
+                // continue from now instead of racing through everything in between
+                let max_skip = (MAX_CATCH_UP_SECS / target_frame_secs) as usize;
+                tracing::debug!(elapsed_time, "resyncing playback clock");
+                schedule.resync(self.clock.now());
+                return (true, frames_to_skip.min(max_skip));
+            }
+            schedule.advance(target_frame_secs * (frames_to_skip + 1) as f64);
             (true, frames_to_skip)
         } else {
             (false, 0)
         }
     }
 
+    /// How long to wait between rendered frames, the frame duration or the `fps_cap`
+    /// interval, whichever is longer.
     fn target_frame_duration(&self) -> Duration {
-        // if negative, will have no frame duration (instant)
-        Duration::from_nanos((1_000_000_000_f64 / self.render_options.fps.max(0_f64)) as u64)
+        secs_to_duration(self.render_interval_secs())
+    }
+
+    fn render_interval_secs(&self) -> f64 {
+        let cap_secs = self
+            .render_options
+            .fps_cap
+            .filter(|cap| *cap > 0.0)
+            .map_or(0.0, |cap| 1.0 / cap);
+        self.target_frame_secs().max(cap_secs)
+    }
+
+    /// How long the current frame is shown in seconds, its own delay for animated images with
+    /// per-frame timing, otherwise derived from the fps. Kept as `f64` since e.g. 1/23.976 s is
+    /// not a whole number of nanoseconds and truncating it would drift over long videos.
+    fn target_frame_secs(&self) -> f64 {
+        self.media
+            .current_frame_duration()
+            .filter(|_| self.render_options.frame_delays)
+            .map_or_else(|| self.fps_frame_secs(), |delay| delay.as_secs_f64())
+    }
+
+    fn fps_frame_secs(&self) -> f64 {
+        // a non-positive (or NaN) fps renders as fast as possible
+        let fps = self.render_options.fps;
+        if fps > 0.0 { 1.0 / fps } else { 0.0 }
     }
 
     pub fn render_frame(&mut self, frame: &DynamicImage) -> Result<RenderFrame, Error> {
-        let procimage = self.pipeline.resize(frame)?;
-        let width = procimage.width();
-        let grayimage = procimage.clone().into_luma8();
-        let rgb_info = procimage.into_rgb8().to_vec();
-
-        if self.pipeline.new_lines {
-            let mut rgb_info_newline = Vec::with_capacity(rgb_info.len() + 6 * width as usize);
-
-            for (i, pixel) in rgb_info.chunks(3).enumerate() {
-                rgb_info_newline.extend_from_slice(pixel);
-                if (i + 1) % width as usize == 0 {
-                    rgb_info_newline.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
-                }
+        let started = Instant::now();
+        let (render_frame, resolution, resize_time) = render_image(&mut self.pipeline, frame)?;
+        self.stats.resolution = resolution;
+        self.stats.resize_time += resize_time;
+        self.stats.convert_time += started.elapsed().saturating_sub(resize_time);
+        Ok(render_frame)
+    }
+
+    fn render_current_frame(&mut self, frame: Option<&DynamicImage>) -> Option<RenderFrame> {
+        if let Some(frame) = frame {
+            self.last_frame = Some(frame.clone());
+        }
+        // without a new frame the last one is shown again
+        let frame = self.last_frame.take()?;
+        let render_frame = self.render_transformed(&frame).ok();
+        self.last_frame = Some(frame);
+        render_frame
+    }
+
+    /// Renders `frame` after the frame transform.
+    fn render_transformed(&mut self, frame: &DynamicImage) -> Result<RenderFrame, Error> {
+        match &self.frame_transform {
+            Some(transform) => {
+                let mut transformed = frame.clone();
+                transform(&mut transformed);
+                self.render_frame(&transformed)
             }
-            return Ok((self.pipeline.to_ascii(&grayimage), rgb_info_newline).into());
+            None => self.render_frame(frame),
         }
-        Ok((self.pipeline.to_ascii(&grayimage), rgb_info).into())
     }
 
-    fn render_current_frame(&mut self, frame: Option<&DynamicImage>) -> Option<RenderFrame> {
-        match frame {
-            Some(frame) => {
-                self.last_frame = Some(frame.clone());
-                if let Ok(render_frame) = self.render_frame(frame) {
-                    return Some(render_frame);
+    fn get_current_frame(&mut self) -> Option<DynamicImage> {
+        let started = Instant::now();
+        let frame = self.media.next();
+        self.stats.decode_time += started.elapsed();
+        frame
+    }
+}
+
+/// Iterator over the rendered frames of a `Renderer`, see `Renderer::frames` and
+/// `Renderer::timed_frames`.
+pub struct Frames {
+    renderer: Renderer,
+    /// When the frames are due, `None` for untimed frames.
+    schedule: Option<FrameSchedule>,
+    allow_frame_skip: bool,
+}
+
+impl Frames {
+    fn new(
+        mut renderer: Renderer,
+        schedule: Option<FrameSchedule>,
+        allow_frame_skip: bool,
+    ) -> Self {
+        renderer.stats.source_fps = renderer.render_options.fps;
+        Self {
+            renderer,
+            schedule,
+            allow_frame_skip,
+        }
+    }
+
+    /// Playback statistics so far.
+    pub fn stats(&self) -> RenderStats {
+        self.renderer.stats
+    }
+
+    /// The renderer, e.g. to change the pipeline between frames.
+    pub fn renderer_mut(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
+}
+
+impl Iterator for Frames {
+    type Item = Result<RenderFrame, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let renderer = &mut self.renderer;
+        if renderer.max_frames_reached() {
+            return None;
+        }
+        let frames_to_skip = match &mut self.schedule {
+            Some(schedule) => loop {
+                let (due, frames_to_skip) = renderer.should_process_frame(schedule);
+                if due {
+                    break frames_to_skip;
                 }
-                None
+                let wait = renderer
+                    .target_frame_duration()
+                    .saturating_sub(renderer.clock.elapsed_since(schedule.due()));
+                std::thread::sleep(wait);
+            },
+            None => 0,
+        };
+
+        let ControlFlow::Continue(frame) =
+            renderer.next_media_frame(self.allow_frame_skip, frames_to_skip)
+        else {
+            return None;
+        };
+        let frame = frame?;
+        let render_frame = renderer.render_transformed(&frame);
+        if render_frame.is_ok() {
+            renderer.stats.frames_rendered += 1;
+        }
+        renderer.last_frame = Some(frame);
+        Some(render_frame)
+    }
+}
+
+/// How far behind schedule (in seconds) playback catches up by skipping frames, falling
+/// further behind means the clock jumped and the schedule is restarted.
+const MAX_CATCH_UP_SECS: f64 = 3.0;
+
+/// When the last frame was due, tracked as seconds since `start` so frame durations add up
+/// exactly instead of accumulating rounding errors.
+struct FrameSchedule {
+    start: Instant,
+    offset: f64,
+}
+
+impl FrameSchedule {
+    fn new(start: Instant) -> Self {
+        Self { start, offset: 0.0 }
+    }
+
+    fn due(&self) -> Instant {
+        let offset = secs_to_duration(self.offset.abs());
+        let due = if self.offset >= 0.0 {
+            self.start.checked_add(offset)
+        } else {
+            self.start.checked_sub(offset)
+        };
+        due.unwrap_or(self.start)
+    }
+
+    /// Makes `now` the due time of the next frame.
+    fn resync(&mut self, now: Instant) {
+        self.start = now;
+        self.offset = 0.0;
+    }
+
+    /// Moves the due time by `secs`, backwards if negative.
+    fn advance(&mut self, secs: f64) {
+        self.offset += secs;
+    }
+}
+
+/// Converts seconds to a `Duration`, saturating instead of panicking on huge or infinite values.
+fn secs_to_duration(secs: f64) -> Duration {
+    Duration::try_from_secs_f64(secs.max(0.0)).unwrap_or(Duration::MAX)
+}
+
+/// Runs `frame` through `pipeline`, returning the rendered frame, its resolution in pixels and
+/// how long resizing took.
+fn render_image(
+    pipeline: &mut ImagePipeline,
+    frame: &DynamicImage,
+) -> Result<(RenderFrame, (u32, u32), Duration), Error> {
+    let started = Instant::now();
+    pipeline.detect_borders(frame);
+    let mut grayimage = pipeline.resize_cached(frame)?;
+    let resize_time = started.elapsed();
+    pipeline.stabilize(&mut grayimage);
+    let (width, height) = (grayimage.width(), grayimage.height());
+    pipeline.calibrate(&grayimage);
+    let text = pipeline.cells_to_ascii(frame, &grayimage)?;
+    let lines = text.split("\r\n").map(String::from).collect();
+    let cell_width = pipeline.cell_width();
+    let fill = pipeline.mode == RenderMode::Blocks;
+    if pipeline.skips_colors() {
+        let render_frame = RenderFrame {
+            lines,
+            colors: Vec::new(),
+            fill,
+            bg_colors: Vec::new(),
+            alphas: Vec::new(),
+        };
+        return Ok((render_frame, (width, height), resize_time));
+    }
+    let cell_colors: Vec<[u8; 3]> = match pipeline.colormap_lut() {
+        Some(lut) => grayimage
+            .pixels()
+            .map(|pixel| lut[pixel.0[0] as usize])
+            .collect(),
+        None => pipeline.resized().pixels().map(|pixel| pixel.0).collect(),
+    };
+
+    let cell_alpha = if pipeline.keep_alpha {
+        pipeline.cell_alpha(frame)?
+    } else {
+        None
+    };
+
+    // one color (and alpha) per char, narrow glyphs are padded with the color of their pixel
+    let mut glyphs = text.chars().filter(|c| !matches!(c, '\r' | '\n'));
+    let mut colors = Vec::with_capacity((width * height) as usize * cell_width);
+    let mut alphas = Vec::new();
+    for (i, pixel) in cell_colors.into_iter().enumerate() {
+        let mut padding = 0;
+        if cell_width > 1 {
+            let glyph_width = glyphs.next().map_or(1, display_width);
+            padding = cell_width.saturating_sub(glyph_width);
+            for _ in 0..padding {
+                glyphs.next();
             }
-            None => {
-                if self.last_frame.is_some()
-                    && let Ok(render_frame) = self.render_frame(
-                        &self
-                            .last_frame
-                            .clone()
-                            .expect("Last frame should be available"),
-                    )
-                {
-                    return Some(render_frame);
-                }
-                None
+        }
+        colors.extend(std::iter::repeat_n(pixel, padding + 1));
+        if let Some(cell_alpha) = &cell_alpha {
+            let alpha = cell_alpha.as_raw().get(i).copied().unwrap_or(u8::MAX);
+            alphas.extend(std::iter::repeat_n(alpha, padding + 1));
+        }
+    }
+    if let Some(palette) = &pipeline.palette {
+        if pipeline.palette_dither {
+            palette.dither(&mut colors, width as usize * cell_width);
+        } else {
+            for rgb in &mut colors {
+                *rgb = palette.nearest(*rgb);
             }
         }
     }
+    let bg_colors = match pipeline.bg_shade {
+        Some(shade) if !fill => colors
+            .iter()
+            .map(|rgb| rgb.map(|c| (c as f32 * shade).clamp(0.0, 255.0) as u8))
+            .collect(),
+        _ => Vec::new(),
+    };
+    Ok((
+        RenderFrame {
+            lines,
+            colors,
+            fill,
+            bg_colors,
+            alphas,
+        },
+        (width, height),
+        resize_time,
+    ))
+}
 
-    fn get_current_frame(&mut self) -> Option<DynamicImage> {
-        self.media.next()
+/// Averages `skipped` into `frame`, frames of a different size are left out.
+fn blend_frames(skipped: &[DynamicImage], frame: DynamicImage) -> DynamicImage {
+    let frame = frame.into_rgb8();
+    let mut sums: Vec<u32> = frame.as_raw().iter().map(|&c| c as u32).collect();
+    let mut count = 1;
+    for skipped in skipped {
+        if skipped.width() != frame.width() || skipped.height() != frame.height() {
+            continue;
+        }
+        for (sum, c) in sums.iter_mut().zip(skipped.to_rgb8().as_raw()) {
+            *sum += *c as u32;
+        }
+        count += 1;
+    }
+
+    let averaged = sums.into_iter().map(|sum| (sum / count) as u8).collect();
+    image::RgbImage::from_raw(frame.width(), frame.height(), averaged)
+        .map_or(DynamicImage::ImageRgb8(frame), DynamicImage::ImageRgb8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, maps::CharMap, pipeline::Resolution};
+
+    fn options(fps: f64) -> RenderOptions {
+        RenderOptions {
+            fps,
+            w_mod: 1,
+            loop_playback: false,
+            start: None,
+            end: None,
+            max_frames: None,
+            frame_delays: false,
+            frame_skip_mode: FrameSkipMode::Drop,
+            fps_cap: None,
+        }
+    }
+
+    /// An animation of 1x1 frames shown for `delays_ms`, frame `i` has the gray level `i * 50`.
+    fn animation(delays_ms: &[u64]) -> FrameIterator {
+        FrameIterator::AnimatedImage {
+            frames: (0..delays_ms.len())
+                .map(|i| {
+                    let gray = image::GrayImage::from_pixel(1, 1, image::Luma([i as u8 * 50]));
+                    DynamicImage::ImageLuma8(gray)
+                })
+                .collect(),
+            delays: delays_ms
+                .iter()
+                .copied()
+                .map(Duration::from_millis)
+                .collect(),
+            current_frame: 0,
+        }
+    }
+
+    /// A renderer of `media` paced by `clock`.
+    fn renderer(media: FrameIterator, options: RenderOptions, clock: &MockClock) -> Renderer {
+        let pipeline = ImagePipeline::new(Resolution::Fixed(1, 1), CharMap::Chars1, true).unwrap();
+        let mut renderer = Renderer::new(pipeline, media, options);
+        renderer.set_clock(clock.clone());
+        renderer
+    }
+
+    #[test]
+    fn frames_are_processed_when_due() {
+        let clock = MockClock::new();
+        let renderer = renderer(animation(&[0; 3]), options(10.0), &clock);
+        let mut schedule = FrameSchedule::new(clock.now());
+
+        assert_eq!(renderer.should_process_frame(&mut schedule), (false, 0));
+        clock.advance(Duration::from_millis(60));
+        assert_eq!(renderer.time_to_send_next_frame(&mut schedule), (false, 0));
+
+        // due at 100ms, on time within the frame
+        clock.advance(Duration::from_millis(90));
+        assert_eq!(renderer.should_process_frame(&mut schedule), (true, 0));
+        assert_eq!(renderer.should_process_frame(&mut schedule), (false, 0));
+
+        // due at 200ms, at 480ms the frames due at 200ms and 300ms are skipped
+        clock.advance(Duration::from_millis(330));
+        assert_eq!(renderer.time_to_send_next_frame(&mut schedule), (true, 2));
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(renderer.time_to_send_next_frame(&mut schedule), (false, 0));
+        clock.advance(Duration::from_millis(30));
+        assert_eq!(renderer.time_to_send_next_frame(&mut schedule), (true, 0));
+    }
+
+    /// Renders a 2x2 image (red, green / blue, white) with the chars ` #`.
+    fn render_2x2(new_lines: bool) -> RenderFrame {
+        let image = image::RgbImage::from_fn(2, 2, |x, y| {
+            image::Rgb(match (x, y) {
+                (0, 0) => [255, 0, 0],
+                (1, 0) => [0, 255, 0],
+                (0, 1) => [0, 0, 255],
+                _ => [255, 255, 255],
+            })
+        });
+        let mut pipeline = ImagePipeline::new(
+            Resolution::Fixed(2, 2),
+            CharMap::custom(" #").unwrap(),
+            new_lines,
+        )
+        .unwrap();
+        RenderFrame::from_image(&mut pipeline, &DynamicImage::ImageRgb8(image)).unwrap()
+    }
+
+    #[test]
+    fn ansi_string_resets_every_row() {
+        assert_eq!(
+            render_2x2(true).to_ansi_string(),
+            "\x1b[38;2;255;0;0m \x1b[38;2;0;255;0m#\x1b[39m\n\
+             \x1b[38;2;0;0;255m \x1b[38;2;255;255;255m#\x1b[39m"
+        );
+    }
+
+    #[test]
+    fn ansi_string_without_new_lines_is_one_row() {
+        assert_eq!(
+            render_2x2(false).to_ansi_string(),
+            "\x1b[38;2;255;0;0m \x1b[38;2;0;255;0m#\
+             \x1b[38;2;0;0;255m \x1b[38;2;255;255;255m#\x1b[39m"
+        );
+    }
+
+    #[test]
+    fn ansi_rows_are_picked_by_index() {
+        let frame = render_2x2(true);
+        let mut out = String::new();
+        frame.push_ansi_row(&mut out, 1, .., ColorDepth::TrueColor);
+        assert_eq!(out, "\x1b[38;2;0;0;255m \x1b[38;2;255;255;255m#\x1b[39m");
+
+        // only the chars in the columns, with their own colors
+        out.clear();
+        frame.push_ansi_row(&mut out, 1, 1..5, ColorDepth::TrueColor);
+        assert_eq!(out, "\x1b[38;2;255;255;255m#\x1b[39m");
+
+        out.clear();
+        frame.push_ansi_row(&mut out, 2, .., ColorDepth::TrueColor);
+        assert_eq!(out, "");
+    }
+
+    /// Every char of an ANSI row with the SGR sequences in effect for it, so outputs that
+    /// only differ in redundant sequences compare equal.
+    fn styled_chars(row: &str) -> Vec<(char, String)> {
+        let mut styled = Vec::new();
+        let mut sgr = String::new();
+        let mut chars = row.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                let sequence: String = chars.by_ref().take_while(|&c| c != 'm').collect();
+                sgr = sequence;
+            } else {
+                styled.push((c, sgr.clone()));
+            }
+        }
+        styled
+    }
+
+    #[test]
+    fn braille_rows_match_a_per_char_encoding() {
+        // runs of two columns share a color, the luminance differs between all of them
+        let image = image::RgbImage::from_fn(4, 2, |x, y| {
+            let level = (x / 2 * 2 + y) as u8 * 60 + 30;
+            image::Rgb([level, 255 - level, 128])
+        });
+        let mut pipeline =
+            ImagePipeline::new(Resolution::Fixed(4, 2), CharMap::Braille, true).unwrap();
+        let frame =
+            RenderFrame::from_image(&mut pipeline, &DynamicImage::ImageRgb8(image)).unwrap();
+        assert!(
+            frame
+                .lines
+                .iter()
+                .all(|line| line.len() > line.chars().count())
+        );
+
+        let ansi_string = frame.to_ansi_string();
+        let ansi_rows: Vec<_> = ansi_string.split('\n').collect();
+        assert_eq!(ansi_rows.len(), 2);
+        for (row, (line, colors)) in frame.rows().enumerate() {
+            let mut reference = String::new();
+            for (c, &rgb) in line.chars().zip(colors) {
+                ColorDepth::TrueColor.push_sgr(&mut reference, rgb, false);
+                reference.push(c);
+            }
+            ColorDepth::TrueColor.push_reset(&mut reference, false);
+
+            let mut pushed = String::new();
+            frame.push_ansi_row(&mut pushed, row, .., ColorDepth::TrueColor);
+            assert_eq!(styled_chars(&pushed), styled_chars(&reference), "row {row}");
+            assert_eq!(ansi_rows[row], pushed);
+            // runs of a color are only introduced once
+            assert!(pushed.len() < reference.len());
+            assert!(pushed.ends_with("\x1b[39m"));
+        }
+    }
+
+    #[test]
+    fn ansi_string_pads_narrow_glyphs_with_their_color() {
+        let image = image::RgbImage::from_fn(2, 1, |x, _| image::Rgb([x as u8 * 255; 3]));
+        let mut pipeline = ImagePipeline::new(
+            Resolution::Fixed(4, 1),
+            CharMap::custom(" 🌕").unwrap(),
+            true,
+        )
+        .unwrap();
+        let frame =
+            RenderFrame::from_image(&mut pipeline, &DynamicImage::ImageRgb8(image)).unwrap();
+        assert_eq!(
+            frame.to_ansi_string(),
+            "\x1b[38;2;0;0;0m  \x1b[38;2;255;255;255m🌕\x1b[39m"
+        );
+    }
+
+    /// A 2x2 image, black on the left and white on the right, rendered as grayscale.
+    fn render_gray_2x2() -> RenderFrame {
+        let image = image::RgbImage::from_fn(2, 2, |x, _| image::Rgb([x as u8 * 255; 3]));
+        let mut pipeline = ImagePipeline::new(
+            Resolution::Fixed(2, 2),
+            CharMap::custom(" #").unwrap(),
+            true,
+        )
+        .unwrap();
+        pipeline.set_grayscale(true);
+        RenderFrame::from_image(&mut pipeline, &DynamicImage::ImageRgb8(image)).unwrap()
+    }
+
+    #[test]
+    fn grayscale_frames_keep_the_terminal_colors() {
+        let frame = render_gray_2x2();
+        assert!(frame.colors.is_empty());
+        assert_eq!(frame.to_ansi_string(), " #\n #");
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn grayscale_frames_draw_in_a_contrasting_color() {
+        let frame = render_gray_2x2();
+        // pixels in `columns` that are bright or else dark
+        let count = |image: &image::RgbaImage, columns: std::ops::Range<u32>, bright: bool| {
+            image
+                .enumerate_pixels()
+                .filter(|(x, _, pixel)| columns.contains(x) && (pixel[0] > 127) == bright)
+                .count()
+        };
+
+        // the glyphs of the right column show up on both backgrounds, the spaces on the left
+        // leave it untouched
+        let on_black = frame.render_to_image(16.0, &[0, 0, 0, 255]);
+        assert_eq!(on_black.dimensions(), (32, 32));
+        assert_eq!(count(&on_black, 0..16, true), 0);
+        assert!(count(&on_black, 16..32, true) > 0);
+
+        let on_white = frame.render_to_image(16.0, &[255, 255, 255, 255]);
+        assert_eq!(count(&on_white, 0..16, false), 0);
+        assert!(count(&on_white, 16..32, false) > 0);
+    }
+
+    #[test]
+    fn fractional_fps_does_not_drift() {
+        let fps = 23.976;
+        let clock = MockClock::new();
+        let renderer = renderer(animation(&[0]), options(fps), &clock);
+        let start = clock.now();
+        let mut schedule = FrameSchedule::new(start);
+
+        // ten minutes in 1ms ticks, counting rendered and skipped frames
+        let mut frames = 0;
+        for _ in 0..10 * 60 * 1000 {
+            clock.advance(Duration::from_millis(1));
+            if let (true, skipped) = renderer.time_to_send_next_frame(&mut schedule) {
+                frames += skipped + 1;
+            }
+        }
+
+        let elapsed = clock.elapsed_since(start).as_secs_f64();
+        let frame_secs = 1.0 / fps;
+        assert!((frames as f64 - elapsed * fps).abs() < 1.0);
+        let scheduled = schedule.due().duration_since(start).as_secs_f64();
+        assert!((scheduled - frames as f64 * frame_secs).abs() < 1e-6);
+        assert!(elapsed - scheduled < frame_secs);
+    }
+
+    #[test]
+    fn clock_jumps_resync_instead_of_bursting() {
+        // 1/8 s is exact in binary, so the cap is exactly 24 frames
+        let fps = 8.0;
+        let clock = MockClock::new();
+        let renderer = renderer(animation(&[0]), options(fps), &clock);
+        let mut schedule = FrameSchedule::new(clock.now());
+
+        // e.g. the system slept for ten seconds
+        clock.advance(Duration::from_secs(10));
+        let max_skip = (MAX_CATCH_UP_SECS / (1.0 / fps)) as usize;
+        assert_eq!(max_skip, 24);
+        assert_eq!(
+            renderer.time_to_send_next_frame(&mut schedule),
+            (true, max_skip)
+        );
+        assert_eq!(schedule.due(), clock.now());
+
+        // the next frames follow at the normal rate from now
+        assert_eq!(renderer.time_to_send_next_frame(&mut schedule), (false, 0));
+        clock.advance(Duration::from_millis(100));
+        assert_eq!(renderer.time_to_send_next_frame(&mut schedule), (false, 0));
+        clock.advance(Duration::from_millis(25));
+        assert_eq!(renderer.time_to_send_next_frame(&mut schedule), (true, 0));
+        assert_eq!(renderer.time_to_send_next_frame(&mut schedule), (false, 0));
+    }
+
+    #[test]
+    fn looping_animations_restart_with_the_first_frame() {
+        let clock = MockClock::new();
+        let mut renderer = renderer(
+            animation(&[100, 200, 300]),
+            RenderOptions {
+                loop_playback: true,
+                frame_delays: true,
+                ..options(10.0)
+            },
+            &clock,
+        );
+        let start = clock.now();
+        let shown = std::cell::RefCell::new(Vec::new());
+        let calls = std::cell::Cell::new(0);
+        renderer
+            .run(false, |state| {
+                if let Some(frame) = state.frame {
+                    let elapsed = clock.elapsed_since(start).as_millis();
+                    shown.borrow_mut().push((frame.colors[0][0] / 50, elapsed));
+                }
+                clock.advance(state.time_until_next_frame);
+                calls.set(calls.get() + 1);
+                shown.borrow().len() < 6 && calls.get() < 100
+            })
+            .unwrap();
+
+        // the first frame waits one frame at the fps, then every frame its own delay
+        assert_eq!(
+            shown.into_inner(),
+            vec![(0, 100), (1, 200), (2, 400), (0, 700), (1, 800), (2, 1000)]
+        );
+    }
+
+    #[test]
+    fn non_positive_fps_renders_as_fast_as_possible() {
+        let clock = MockClock::new();
+        for fps in [0.0, -5.0, f64::NAN, f64::NEG_INFINITY, f64::INFINITY] {
+            let renderer = renderer(animation(&[0]), options(fps), &clock);
+            assert_eq!(renderer.fps_frame_secs(), 0.0, "fps {fps}");
+            assert_eq!(renderer.target_frame_duration(), Duration::ZERO);
+
+            // every frame is due right away and nothing is ever skipped
+            let mut schedule = FrameSchedule::new(clock.now());
+            assert_eq!(renderer.time_to_send_next_frame(&mut schedule), (true, 0));
+            clock.advance(Duration::from_secs(10));
+            assert_eq!(renderer.time_to_send_next_frame(&mut schedule), (true, 0));
+        }
+    }
+
+    #[test]
+    fn skipping_animated_frames_follows_their_delays() {
+        let clock = MockClock::new();
+        let mut renderer = renderer(
+            animation(&[100, 400, 100, 100]),
+            RenderOptions {
+                frame_delays: true,
+                ..options(10.0)
+            },
+            &clock,
+        );
+        let gray =
+            |frame: Option<DynamicImage>| frame.map(|frame| frame.to_luma8()[(0, 0)][0] / 50);
+        let mut next = |frames_to_skip| match renderer.next_media_frame(true, frames_to_skip) {
+            ControlFlow::Continue(frame) => gray(frame),
+            ControlFlow::Break(()) => panic!("playback ended"),
+        };
+
+        assert_eq!(next(0), Some(0));
+        // 200ms behind is within the 400ms of the second frame, which is not skipped
+        assert_eq!(next(2), Some(1));
+        // 800ms behind skips the rest of the animation instead of wrapping around
+        assert_eq!(next(2), None);
+        assert_eq!(next(0), None);
+    }
+
+    #[test]
+    fn only_produced_frames_are_counted() {
+        let clock = MockClock::new();
+        let options = || RenderOptions {
+            end: Some(Duration::from_millis(150)),
+            ..options(10.0)
+        };
+        // playback stops at `end` without counting a third frame
+        let mut frames = renderer(animation(&[100, 100, 100]), options(), &clock).frames();
+        assert_eq!(frames.by_ref().count(), 2);
+        assert_eq!(frames.stats().frames_rendered, 2);
+
+        // without any frame there is nothing to render
+        let stats = std::cell::Cell::new(RenderStats::default());
+        renderer(animation(&[]), options(), &clock)
+            .run(false, |state| {
+                clock.advance(state.time_until_next_frame);
+                stats.set(state.stats);
+                true
+            })
+            .unwrap();
+        assert_eq!(stats.get().frames_rendered, 0);
     }
 }
@@ -0,0 +1,117 @@
+//! Luminance lookup tables (256 entries, indexed by luma) used to adjust the tonal
+//! distribution before the char map lookup.
+
+use image::GrayImage;
+
+/// Counts the pixels of every luma value.
+pub(crate) fn histogram(input: &GrayImage) -> [u64; 256] {
+    let mut histogram = [0u64; 256];
+    for pixel in input.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+    histogram
+}
+
+/// The histogram the pixels would have after applying `lut`.
+pub(crate) fn remap_histogram(histogram: &[u64; 256], lut: Option<[u8; 256]>) -> [u64; 256] {
+    let Some(lut) = lut else {
+        return *histogram;
+    };
+
+    let mut remapped = [0u64; 256];
+    for (i, count) in histogram.iter().enumerate() {
+        remapped[lut[i] as usize] += count;
+    }
+    remapped
+}
+
+/// Applies `first`, then `second`, `None` if neither is set.
+pub(crate) fn compose(first: Option<[u8; 256]>, second: Option<[u8; 256]>) -> Option<[u8; 256]> {
+    match (first, second) {
+        (Some(first), Some(second)) => Some(first.map(|v| second[v as usize])),
+        (lut, None) | (None, lut) => lut,
+    }
+}
+
+/// Builds a histogram equalization lookup table, spreading the luma across the full range.
+pub(crate) fn equalization_lut(histogram: &[u64; 256]) -> [u8; 256] {
+    let mut cdf = [0u64; 256];
+    let mut acc = 0;
+    for (i, count) in histogram.iter().enumerate() {
+        acc += count;
+        cdf[i] = acc;
+    }
+
+    let total = acc;
+    let cdf_min = cdf.iter().copied().find(|&c| c > 0).unwrap_or(0);
+    let mut lut = [0u8; 256];
+    for (i, value) in lut.iter_mut().enumerate() {
+        *value = if total > cdf_min {
+            (cdf[i].saturating_sub(cdf_min) * u8::MAX as u64 / (total - cdf_min)) as u8
+        } else {
+            // uniform image, nothing to spread
+            i as u8
+        };
+    }
+    lut
+}
+
+/// The luma values at the `low` and `high` percentiles (0.0..=1.0) of `histogram`.
+pub(crate) fn percentiles(histogram: &[u64; 256], low: f64, high: f64) -> (usize, usize) {
+    let total: u64 = histogram.iter().sum();
+    let percentile = |p: f64| {
+        let target = (total as f64 * p).ceil().max(1.0) as u64;
+        let mut acc = 0;
+        for (i, count) in histogram.iter().enumerate() {
+            acc += count;
+            if acc >= target {
+                return i;
+            }
+        }
+        u8::MAX as usize
+    };
+    (percentile(low), percentile(high))
+}
+
+/// Builds a lookup table linearly stretching the luma between `lo` and `hi` to the full
+/// range, clipping the rest.
+pub(crate) fn stretch_lut(lo: usize, hi: usize) -> [u8; 256] {
+    let mut lut = [0u8; 256];
+    for (i, value) in lut.iter_mut().enumerate() {
+        *value = if hi > lo {
+            (i.saturating_sub(lo) * u8::MAX as usize / (hi - lo)).min(u8::MAX as usize) as u8
+        } else {
+            // flat image, nothing to stretch
+            i as u8
+        };
+    }
+    lut
+}
+
+/// Builds a lookup table linearly interpolating between `points` (input, output), sorted by
+/// input. Luma outside the first and last point keeps their output.
+pub(crate) fn curve_lut(points: &[(u8, u8)]) -> [u8; 256] {
+    let mut lut = [0u8; 256];
+    let (Some(&first), Some(&last)) = (points.first(), points.last()) else {
+        return std::array::from_fn(|i| i as u8);
+    };
+    for (i, value) in lut.iter_mut().enumerate() {
+        let i = i as u8;
+        *value = if i <= first.0 {
+            first.1
+        } else if i >= last.0 {
+            last.1
+        } else {
+            // the segment `i` falls into, found since it lies between the first and last point
+            points
+                .windows(2)
+                .find(|pair| i <= pair[1].0)
+                .map_or(i, |pair| {
+                    let [(x0, y0), (x1, y1)] = [pair[0], pair[1]];
+                    let t = (i - x0) as f32 / (x1 - x0) as f32;
+                    (y0 as f32 + t * (y1 as f32 - y0 as f32)).round() as u8
+                })
+        };
+    }
+    lut
+}
@@ -1,4 +1,5 @@
 use std::{
+    io,
     path::Path,
     process::{Command, Stdio},
     str::FromStr,
@@ -6,15 +7,33 @@ use std::{
 
 use image::{DynamicImage, ImageBuffer};
 use num::{Rational64, ToPrimitive};
-use opencv::{core::*, imgproc};
+use opencv::{core::*, imgproc, prelude::*, videoio::VideoCapture};
 use serde_json::Value;
 
-pub fn extract_fps(video_path: &Path) -> Option<f64> {
+use crate::error::{ERROR_OPENING_VIDEO, Error};
+
+/// Maps a failure to spawn `tool` to `Error::MissingDependency` when it isn't installed.
+fn spawn_error(tool: &str, hint: &str, error: io::Error) -> Error {
+    if error.kind() == io::ErrorKind::NotFound {
+        Error::MissingDependency {
+            tool: tool.to_string(),
+            hint: hint.to_string(),
+        }
+    } else {
+        error.into()
+    }
+}
+
+/// Queries the frame rate of video stream `stream` with ffprobe.
+/// Fails if a stream other than the first is requested but doesn't exist, or with
+/// `Error::MissingDependency` if ffprobe isn't installed. The frame rate is optional, so
+/// callers that have another source for it (e.g. OpenCV) can carry on without ffprobe.
+pub fn extract_fps(video_path: &Path, stream: usize) -> Result<Option<f64>, Error> {
     let output = Command::new("ffprobe")
         .arg("-v")
         .arg("error")
         .arg("-select_streams")
-        .arg("v:0")
+        .arg(format!("v:{stream}"))
         .arg("-show_entries")
         .arg("stream=r_frame_rate")
         .arg("-of")
@@ -23,11 +42,27 @@ pub fn extract_fps(video_path: &Path) -> Option<f64> {
         .stdout(Stdio::piped())
         .stderr(Stdio::null())
         .output()
-        .expect("Failed to extract fps from video. Is ffprobe installed?");
+        .map_err(|e| {
+            spawn_error(
+                "ffprobe",
+                "install ffmpeg (which ships ffprobe) and make sure it is on your PATH",
+                e,
+            )
+        })?;
 
     let output_str = String::from_utf8(output.stdout).unwrap_or("".to_string());
     let json_value: Value = serde_json::from_str(&output_str).unwrap_or(Value::Null);
     if json_value != Value::Null {
+        if stream > 0
+            && json_value["streams"]
+                .as_array()
+                .is_none_or(|streams| streams.is_empty())
+        {
+            return Err(Error::Application(format!(
+                "{ERROR_OPENING_VIDEO}: video stream {stream} does not exist"
+            )));
+        }
+
         let r_frame_rate = json_value["streams"][0]["r_frame_rate"]
             .as_str()
             .ok_or("".to_string())
@@ -35,39 +70,131 @@ pub fn extract_fps(video_path: &Path) -> Option<f64> {
 
         let frame_rate_f = Rational64::from_str(r_frame_rate);
         if let Ok(frame_rate) = frame_rate_f {
-            return Some(frame_rate.to_f64().expect("Failed to parse FPS value"));
+            tracing::debug!(r_frame_rate, "ffprobe frame rate");
+            return Ok(frame_rate.to_f64());
+        }
+        tracing::debug!(r_frame_rate, "unparsable ffprobe frame rate");
+    } else {
+        tracing::debug!(status = %output.status, "ffprobe returned no json");
+    }
+
+    Ok(None)
+}
+
+/// Whether ffprobe finds a video stream in `video_path`, files it cannot read at all have none.
+/// Fails with `Error::MissingDependency` if ffprobe isn't installed.
+pub fn has_video_stream(video_path: &Path) -> Result<bool, Error> {
+    let output = Command::new("ffprobe")
+        .arg("-v")
+        .arg("error")
+        .arg("-select_streams")
+        .arg("v")
+        .arg("-show_entries")
+        .arg("stream=index")
+        .arg("-of")
+        .arg("json")
+        .arg(video_path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|e| {
+            spawn_error(
+                "ffprobe",
+                "install ffmpeg (which ships ffprobe) and make sure it is on your PATH",
+                e,
+            )
+        })?;
+
+    let json_value: Value = serde_json::from_slice(&output.stdout).unwrap_or(Value::Null);
+    Ok(json_value["streams"]
+        .as_array()
+        .is_some_and(|streams| !streams.is_empty()))
+}
+
+/// Measures the average frame rate of the first `frames` frames of `video` from their
+/// timestamps, then rewinds it. `None` if the video has too few frames or no timestamps.
+pub fn sample_fps(video: &mut VideoCapture, frames: usize) -> Option<f64> {
+    let mut timestamps = Vec::with_capacity(frames);
+    for _ in 0..frames {
+        // grabbing skips decoding the frames
+        if !video.grab().unwrap_or(false) {
+            break;
+        }
+        if let Ok(msec) = video.get(opencv::videoio::CAP_PROP_POS_MSEC) {
+            timestamps.push(msec);
         }
     }
+    let _ = video.set(opencv::videoio::CAP_PROP_POS_FRAMES, 0.0);
 
-    None
+    let (first, last) = (*timestamps.first()?, *timestamps.last()?);
+    let span_secs = (last - first) / 1000.0;
+    (timestamps.len() > 1 && span_secs > 0.0).then(|| (timestamps.len() - 1) as f64 / span_secs)
 }
 
+/// Converts an OpenCV frame (gray, BGR or BGRA) to RGB. Frames with more than 8 bits per
+/// channel (16 bit or float, e.g. from HDR sources) are tone-mapped down to 8 bits.
 pub fn mat_to_dynamic_image(mat: &Mat) -> Option<DynamicImage> {
+    let code = match mat.channels() {
+        1 => imgproc::COLOR_GRAY2RGB,
+        3 => imgproc::COLOR_BGR2RGB,
+        4 => imgproc::COLOR_BGRA2RGB,
+        _ => return None,
+    };
+
+    // color conversion doesn't support doubles
+    let mut float_mat = Mat::default();
+    let mat = if mat.depth() == CV_64F {
+        mat.convert_to(&mut float_mat, CV_32F, 1.0, 0.0).ok()?;
+        &float_mat
+    } else {
+        mat
+    };
+
     let mut rgb_mat = Mat::default();
-    if imgproc::cvt_color(
+    imgproc::cvt_color(
         &mat,
         &mut rgb_mat,
-        imgproc::COLOR_BGR2RGB,
+        code,
         0,
         AlgorithmHint::ALGO_HINT_DEFAULT,
     )
-    .is_ok()
-        && rgb_mat.elem_size().is_ok()
-        && let Ok(size) = rgb_mat.size()
-    {
-        let reshaped_mat = rgb_mat.reshape(1, size.width * size.height).ok()?;
-        let data_vec: Vec<u8> = reshaped_mat
-            .data_typed::<u8>()
-            .expect("Unexpected invalid data")
-            .to_vec();
-
-        if let Some(img_buf) = ImageBuffer::<image::Rgb<u8>, _>::from_raw(
-            size.width as u32,
-            size.height as u32,
-            data_vec,
-        ) {
-            return Some(DynamicImage::ImageRgb8(img_buf));
-        }
-    }
-    None
+    .ok()?;
+    let size = rgb_mat.size().ok()?;
+    let reshaped_mat = rgb_mat.reshape(1, size.width * size.height).ok()?;
+    let data_vec: Vec<u8> = match rgb_mat.depth() {
+        CV_8U => reshaped_mat.data_typed::<u8>().ok()?.to_vec(),
+        CV_16U => tone_map(
+            reshaped_mat
+                .data_typed::<u16>()
+                .ok()?
+                .iter()
+                .map(|&v| v as f32 / u16::MAX as f32)
+                .collect(),
+        ),
+        CV_32F => tone_map(reshaped_mat.data_typed::<f32>().ok()?.to_vec()),
+        _ => return None,
+    };
+
+    ImageBuffer::<image::Rgb<u8>, _>::from_raw(size.width as u32, size.height as u32, data_vec)
+        .map(DynamicImage::ImageRgb8)
+}
+
+/// Maps linear values to 8 bits with the extended Reinhard operator. The brightest value of
+/// the frame (at least 1.0) becomes white, so values within 0..=1 are mapped linearly and only
+/// HDR highlights are compressed.
+fn tone_map(values: Vec<f32>) -> Vec<u8> {
+    let white = values
+        .iter()
+        .copied()
+        .filter(|v| v.is_finite())
+        .fold(1_f32, f32::max);
+    let white_sq = white * white;
+    values
+        .into_iter()
+        .map(|v| {
+            let v = if v.is_finite() { v.max(0.0) } else { 0.0 };
+            let mapped = v * (1.0 + v / white_sq) / (1.0 + v);
+            (mapped * u8::MAX as f32).round().clamp(0.0, u8::MAX as f32) as u8
+        })
+        .collect()
 }
@@ -0,0 +1,60 @@
+//! A ratatui widget for `RenderFrame`, to show the art in a pane next to other widgets instead
+//! of handing the whole terminal to a player.
+//!
+//! Frames come out of `Renderer::run` through the callback, keep the latest one and draw it
+//! with the rest of the UI:
+//!
+//! ```ignore
+//! renderer.run(false, |state| {
+//!     if let Some(frame) = state.frame {
+//!         let _ = terminal.draw(|f| f.render_widget(&frame, f.area()));
+//!     }
+//!     true // return false to stop playback, e.g. on a key press
+//! })?;
+//! ```
+//!
+//! Build the pipeline with `new_lines(true)` and the pane size as `Resolution::Fixed`, so every
+//! row of the frame maps to one row of the pane.
+
+use ratatui::{buffer::Buffer, layout::Rect, style::Color, widgets::Widget};
+
+use crate::{maps::display_width, render::RenderFrame};
+
+impl Widget for &RenderFrame {
+    /// Draws the frame into the top left of `area`, clipping whatever does not fit.
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let rows = self.rows().zip(self.bg_rows());
+        for (((line, colors), bg_colors), y) in rows.zip(area.top()..area.bottom()) {
+            let mut x = area.left();
+            for (i, c) in line.chars().enumerate() {
+                let width = display_width(c).max(1) as u16;
+                if x + width > area.right() {
+                    break;
+                }
+                let cell = &mut buf[(x, y)];
+                cell.set_char(c);
+                // grayscale frames have no colors, their chars keep the style of the cell
+                let Some(&[r, g, b]) = colors.get(i) else {
+                    x += width;
+                    continue;
+                };
+                let color = Color::Rgb(r, g, b);
+                if self.fill {
+                    cell.set_bg(color);
+                } else {
+                    cell.set_fg(color);
+                    if let Some(&[r, g, b]) = bg_colors.get(i) {
+                        cell.set_bg(Color::Rgb(r, g, b));
+                    }
+                }
+                x += width;
+            }
+        }
+    }
+}
+
+impl Widget for RenderFrame {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        (&self).render(area, buf);
+    }
+}
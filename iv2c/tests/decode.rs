@@ -0,0 +1,95 @@
+use std::{path::PathBuf, time::Duration};
+
+use image::{DynamicImage, GenericImageView};
+use iv2c::frames::{FpsSource, FrameIterator, MediaData, open_media_from_path};
+
+fn open_fixture(name: &str) -> MediaData {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    open_media_from_path(&path).unwrap()
+}
+
+/// Pulls every frame together with how long it is shown.
+fn frames(frame_iter: &mut FrameIterator) -> Vec<(DynamicImage, Option<Duration>)> {
+    let mut frames = Vec::new();
+    while let Some(frame) = frame_iter.next() {
+        frames.push((frame, frame_iter.current_frame_duration()));
+    }
+    frames
+}
+
+/// The color of the top left pixel of `frame`.
+fn top_left(frame: &DynamicImage) -> [u8; 3] {
+    frame.to_rgb8().get_pixel(0, 0).0
+}
+
+fn millis(delays: &[u64]) -> Vec<Duration> {
+    delays.iter().copied().map(Duration::from_millis).collect()
+}
+
+#[test]
+fn png_is_a_single_untimed_frame() {
+    let mut media = open_fixture("still.png");
+    assert_eq!(media.frame_count(), Some(1));
+    assert_eq!(media.dimensions(), Some((2, 2)));
+    assert_eq!(media.fps, None);
+    assert_eq!(media.fps_source, FpsSource::None);
+    assert_eq!(media.frame_delays(), None);
+
+    let frames = frames(&mut media.frame_iter);
+    assert_eq!(frames.len(), 1);
+    let (frame, delay) = &frames[0];
+    assert_eq!((frame.width(), frame.height()), (2, 2));
+    assert_eq!(frame.to_rgb8().get_pixel(1, 1).0, [255, 255, 255]);
+    assert_eq!(*delay, None);
+}
+
+#[test]
+fn gif_frames_keep_their_delays() {
+    let mut media = open_fixture("three_frames.gif");
+    assert_eq!(media.frame_count(), Some(3));
+    assert_eq!(media.dimensions(), Some((2, 2)));
+    assert_eq!(media.fps_source, FpsSource::FrameDelays);
+    // the average over 600ms
+    assert_eq!(media.fps, Some(5.0));
+    assert_eq!(media.frame_delays(), Some(&millis(&[100, 200, 300])[..]));
+    assert_eq!(media.duration(), Some(Duration::from_millis(600)));
+
+    let frames = frames(&mut media.frame_iter);
+    let colors: Vec<_> = frames.iter().map(|(frame, _)| top_left(frame)).collect();
+    assert_eq!(colors, [[255, 0, 0], [0, 255, 0], [0, 0, 255]]);
+    assert!(frames.iter().all(|(frame, _)| frame.dimensions() == (2, 2)));
+    let delays: Vec<_> = frames.iter().filter_map(|(_, delay)| *delay).collect();
+    assert_eq!(delays, millis(&[100, 200, 300]));
+}
+
+#[test]
+fn webp_frames_keep_their_delays() {
+    // loops forever, which must not unroll into more (or fewer) frames
+    let mut media = open_fixture("two_frames.webp");
+    assert_eq!(media.frame_count(), Some(2));
+    assert_eq!(media.dimensions(), Some((2, 2)));
+    assert_eq!(media.fps_source, FpsSource::FrameDelays);
+    assert!(media.fps.is_some_and(|fps| fps > 0.0));
+    assert_eq!(media.frame_delays(), Some(&millis(&[100, 250])[..]));
+
+    let frames = frames(&mut media.frame_iter);
+    let colors: Vec<_> = frames.iter().map(|(frame, _)| top_left(frame)).collect();
+    assert_eq!(colors, [[255, 0, 0], [0, 0, 255]]);
+    assert!(frames.iter().all(|(frame, _)| frame.dimensions() == (2, 2)));
+    let delays: Vec<_> = frames.iter().filter_map(|(_, delay)| *delay).collect();
+    assert_eq!(delays, millis(&[100, 250]));
+}
+
+#[test]
+fn webp_with_a_loop_count_decodes_a_single_pass() {
+    // the same animation as `two_frames.webp`, but with a loop count of 3
+    let mut media = open_fixture("three_loops.webp");
+    assert_eq!(media.frame_count(), Some(2));
+    assert_eq!(media.frame_delays(), Some(&millis(&[100, 250])[..]));
+    assert_eq!(media.duration(), Some(Duration::from_millis(350)));
+    // the average over a single pass
+    assert!(media.fps.is_some_and(|fps| (fps - 2.0 / 0.35).abs() < 1e-9));
+    assert_eq!(frames(&mut media.frame_iter).len(), 2);
+}
@@ -0,0 +1,231 @@
+//! Full fidelity playback with terminal graphics protocols (Kitty, iTerm2 inline images)
+//! instead of characters.
+
+use std::{
+    io::{Cursor, Write, stdout},
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor::MoveTo,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute, queue,
+    style::Print,
+    terminal,
+};
+use image::{DynamicImage, ImageFormat, imageops::FilterType};
+use iv2c::{error::Error, frames::MediaData};
+
+use crate::{Args, resolve_fps, terminal_player::TerminalPlayer};
+
+/// Max base64 payload per Kitty escape sequence.
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+/// Pixel size of a terminal cell when the terminal doesn't report its size in pixels.
+const FALLBACK_CELL_PX: (u32, u32) = (10, 20);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    Kitty,
+    ITerm2,
+}
+
+impl Protocol {
+    /// The protocol the terminal supports, going by the environment it sets.
+    fn detect() -> Option<Self> {
+        let env = |name: &str| std::env::var(name).unwrap_or_default();
+        if !env("KITTY_WINDOW_ID").is_empty() || env("TERM").contains("kitty") {
+            Some(Protocol::Kitty)
+        } else if matches!(env("TERM_PROGRAM").as_str(), "iTerm.app" | "WezTerm") {
+            Some(Protocol::ITerm2)
+        } else {
+            None
+        }
+    }
+}
+
+/// Plays the media as images fitted to the terminal until it ends (or forever with `--loop`),
+/// keeping the last frame until `q`, `Esc` or `Ctrl+C`.
+pub fn play(args: Args, media_data: MediaData) -> Result<(), Error> {
+    let protocol = Protocol::detect().ok_or(Error::Application(
+        "--mode kitty needs a terminal supporting the Kitty graphics protocol or iTerm2 inline \
+         images (detected from $KITTY_WINDOW_ID, $TERM and $TERM_PROGRAM)"
+            .to_string(),
+    ))?;
+    let fps = resolve_fps(&args, media_data.fps);
+    let fps_secs = if fps > 0.0 { 1.0 / fps } else { 0.0 };
+    let mut media = media_data.frame_iter;
+    if let Some(position) = args.seek.or(args.start) {
+        media.seek(position, fps);
+    }
+
+    let mut term = TerminalPlayer::new("iv2c".to_string(), false).with_inline(args.inline);
+    term.install_signal_handler()?;
+    term.init()?;
+
+    let mut due = Instant::now();
+    let mut shown = 0;
+    let mut ended = false;
+    // whether the media was just rewound, so media that is empty after rewinding ends
+    let mut rewound = false;
+    loop {
+        if !ended && args.frames.is_none_or(|frames| shown < frames) {
+            match media.next() {
+                Some(frame) => {
+                    rewound = false;
+                    draw(protocol, &frame, term.origin())?;
+                    shown += 1;
+                    // animated images keep their own delays unless --fps is forced
+                    let delay = media
+                        .current_frame_duration()
+                        .filter(|_| args.fps.is_none())
+                        .unwrap_or_else(|| Duration::from_secs_f64(fps_secs));
+                    due += delay;
+                }
+                None if args.r#loop && !rewound => {
+                    rewound = true;
+                    media.reset();
+                    if let Some(start) = args.start {
+                        media.seek(start, fps);
+                    }
+                    continue;
+                }
+                None => ended = true,
+            }
+        }
+
+        let timeout = if ended {
+            Duration::from_millis(100)
+        } else {
+            due.saturating_duration_since(Instant::now())
+        };
+        if quit_requested(timeout) {
+            break;
+        }
+    }
+
+    if protocol == Protocol::Kitty {
+        // delete the images, they would otherwise stay on screen with --inline
+        execute!(stdout(), Print("\x1b_Ga=d,d=A,q=2\x1b\\"))?;
+    }
+    Ok(())
+}
+
+/// Waits up to `timeout` for a key that quits.
+fn quit_requested(timeout: Duration) -> bool {
+    if !event::poll(timeout).unwrap_or(false) {
+        return false;
+    }
+    matches!(
+        event::read(),
+        Ok(Event::Key(
+            KeyEvent {
+                code: KeyCode::Char('q' | 'Q') | KeyCode::Esc,
+                ..
+            } | KeyEvent {
+                code: KeyCode::Char('c' | 'C'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            }
+        ))
+    )
+}
+
+/// Draws `frame` fitted to the terminal from row `origin`, replacing the last one.
+fn draw(protocol: Protocol, frame: &DynamicImage, origin: u16) -> Result<(), Error> {
+    let (width, height) = terminal_pixels()?;
+    let frame = frame.resize(width, height, FilterType::Triangle);
+    let mut png = Vec::new();
+    frame
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|err| Error::decode("Failed to encode frame", err))?;
+    let data = base64(&png);
+
+    let mut out = stdout().lock();
+    queue!(out, MoveTo(0, origin))?;
+    match protocol {
+        Protocol::Kitty => {
+            // the same image and placement id replace the previous frame
+            let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let more = u8::from(i + 1 < chunks.len());
+                let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+                if i == 0 {
+                    write!(
+                        out,
+                        "\x1b_Ga=T,f=100,i=1,p=1,q=2,C=1,m={more};{chunk}\x1b\\"
+                    )?;
+                } else {
+                    write!(out, "\x1b_Gm={more};{chunk}\x1b\\")?;
+                }
+            }
+        }
+        Protocol::ITerm2 => {
+            write!(
+                out,
+                "\x1b]1337;File=inline=1;size={};width={}px;height={}px;preserveAspectRatio=1:{data}\x07",
+                png.len(),
+                frame.width(),
+                frame.height()
+            )?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Size of the terminal window in pixels, estimated from the cells if it isn't reported.
+fn terminal_pixels() -> Result<(u32, u32), Error> {
+    let size = terminal::window_size()?;
+    if size.width > 0 && size.height > 0 {
+        return Ok((size.width as u32, size.height as u32));
+    }
+    Ok((
+        size.columns as u32 * FALLBACK_CELL_PX.0,
+        size.rows as u32 * FALLBACK_CELL_PX.1,
+    ))
+}
+
+/// Standard base64 with padding.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_matches_the_rfc_4648_test_vectors() {
+        for (data, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64(data.as_bytes()), encoded, "{data:?}");
+        }
+        // the last two characters of the alphabet
+        assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
+    }
+}
@@ -1,34 +1,82 @@
 use clap::{Parser, ValueEnum};
-use iv2c::error::Error;
-use iv2c::frames::{MediaData, open_media_from_path};
-use iv2c::maps::CharMap;
-use iv2c::pipeline::{ImagePipeline, Resolution};
-use iv2c::render::{RenderFrame, RenderOptions};
+use image::DynamicImage;
+use iv2c::color::{ColorDepth, Colormap, Palette, parse_hex};
+use iv2c::error::{ERROR_DATA, Error};
+use iv2c::frames::{FpsSource, FrameIterator, MediaData, OpenOptions, open_media};
+use iv2c::maps::{CharMap, display_width};
+use iv2c::pipeline::{
+    AutoCrop, AutoLevels, Crop, Equalization, ImagePipeline, RenderMode, Resolution, SamplePoint,
+    ToneCurve,
+};
+use iv2c::render::{FrameSkipMode, RenderFrame, RenderOptions, Renderer};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
+mod graphics;
+#[cfg(feature = "server")]
+mod server;
 mod terminal_player;
 
 /// Command line arguments structure.
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// Play or Export
-    #[arg(value_enum, required = true)]
-    action: Action,
-    /// Name of the file/stream to process
-    #[arg(required = true)]
-    input: String,
+    #[arg(value_enum, required_unless_present = "dump_chars")]
+    action: Option<Action>,
+    /// Name of the file/stream to process, or a directory of images to play as an animation.
+    /// Export takes several inputs with --out-dir
+    #[arg(required_unless_present = "dump_chars", num_args = 1..)]
+    inputs: Vec<String>,
     // Name of the file to output to
     #[arg(short, long)]
     output: Option<String>,
-    /// Force a user-specified FPS
-    #[arg(short, long)]
-    fps: Option<String>,
+    /// Export every input into this directory, named after the input with the --out-ext
+    /// extension (`png` writes a directory of frames per input)
+    #[arg(long, value_name = "DIR", conflicts_with = "output")]
+    out_dir: Option<PathBuf>,
+    /// Extension (and so format) of the files written to --out-dir: txt, ndjson, jsonl or png
+    #[arg(long, value_name = "EXT", default_value = "txt", requires = "out_dir")]
+    out_ext: String,
+    /// Force a user-specified FPS, can speed up or slow down playback
+    #[arg(short, long, value_parser = parse_fps)]
+    fps: Option<f64>,
+    /// Measure the fps of videos from the timestamps of the first frames and use it if it differs
+    /// from the reported one (variable frame rate content), costs a pass over them when opening
+    #[arg(long, default_value_t = false)]
+    detect_fps: bool,
+    /// Render at most N frames per second, the lower of N and the source fps (or --fps). Playback
+    /// speed is unchanged with --allow-frame-skip, which skips the frames in between, otherwise
+    /// playback slows down
+    #[arg(long, visible_alias = "max-fps", value_name = "N", value_parser = parse_fps)]
+    fps_cap: Option<f64>,
+    /// Show a still image as N identical frames (timed by --fps), e.g. to export it as a clip
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    repeat: Option<u64>,
+    /// Show a still image for this long (seconds, mm:ss or hh:mm:ss) at --fps, like --repeat
+    #[arg(long, value_parser = parse_timestamp, conflicts_with = "repeat")]
+    repeat_duration: Option<Duration>,
     /// Loop playing of video/gif
     #[arg(short, long, default_value_t = false)]
     r#loop: bool,
     /// Custom lookup char table
     #[arg(short, long)]
     char_map: Option<String>,
+    /// Print every char of the resolved char map (and bands) with its index and code point, then
+    /// exit
+    #[arg(long, default_value_t = false)]
+    dump_chars: bool,
+    /// Use other chars for a luminance band, LOW-HIGH:CHARS (e.g. 0-30:" "), can be repeated
+    #[arg(long, value_parser = parse_band)]
+    band: Vec<(RangeInclusive<u8>, String)>,
+    /// Log more details to stderr (-v info, -vv debug, -vvv trace), redirect stderr while playing
+    #[arg(short, long, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+    /// Only log errors
+    #[arg(short, long, default_value_t = false)]
+    quiet: bool,
+    /// Print `info` as JSON
+    #[arg(long, default_value_t = false)]
+    json: bool,
     /// Grayscale mode
     #[arg(short, long, default_value_t = false)]
     gray: bool,
@@ -38,9 +86,162 @@ struct Args {
     /// Experimental frame skip flag
     #[arg(short, long, default_value_t = false)]
     allow_frame_skip: bool,
-    /// Experimental flag to add newlines
+    /// Blend the skipped frames into the rendered one instead of dropping them
+    #[arg(long, default_value_t = false, requires = "allow_frame_skip")]
+    blend_skipped: bool,
+    /// Experimental flag to add newlines at the end of each row. In terminal playback every
+    /// row is positioned explicitly either way; without it the frame wraps at the terminal width
     #[arg(short, long, default_value_t = false)]
     new_lines: bool,
+    /// Index of the video stream to decode, for files with several video tracks
+    #[arg(long, default_value_t = 0)]
+    stream: usize,
+    /// Memory budget for decoding GIF/WebP frames up front, in bytes (suffixes K, M, G)
+    #[arg(long, env = "IV2C_MAX_DECODE_MEM", value_parser = parse_bytes)]
+    max_decode_mem: Option<usize>,
+    /// Keep the frames decoded so far when --max-decode-mem is exceeded instead of failing
+    #[arg(long, default_value_t = false, requires = "max_decode_mem")]
+    truncate_decode: bool,
+    /// Failed or empty video reads to retry, waiting longer each time (5 ms doubling up to
+    /// 500 ms), before a stream counts as ended (network streams)
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    read_retries: u32,
+    /// Start position (seconds, mm:ss or hh:mm:ss), ignored for single images
+    #[arg(long, value_parser = parse_timestamp)]
+    start: Option<Duration>,
+    /// End position (seconds, mm:ss or hh:mm:ss), ignored for single images
+    #[arg(long, value_parser = parse_timestamp)]
+    end: Option<Duration>,
+    /// Jump to this position (seconds, mm:ss or hh:mm:ss) before playback starts, looping still
+    /// restarts at --start
+    #[arg(long, value_parser = parse_timestamp)]
+    seek: Option<Duration>,
+    /// Render at a fixed WxH character grid instead of the terminal size (e.g. 120x40)
+    #[arg(long, value_parser = parse_resolution)]
+    output_resolution: Option<(u32, u32)>,
+    /// Render exactly N characters wide, the height follows from the aspect ratio unless
+    /// --height is given too
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "output_resolution")]
+    width: Option<u32>,
+    /// Render exactly N rows high, the width follows from the aspect ratio unless --width is
+    /// given too
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "output_resolution")]
+    height: Option<u32>,
+    /// Crop dark borders (letterboxing), detected over the first FRAMES frames (default 30)
+    #[arg(long, value_name = "FRAMES", num_args = 0..=1, default_missing_value = "30")]
+    autocrop: Option<u32>,
+    /// How cells sample the source when downscaling: `area` averages every pixel a cell covers
+    /// (smooth, reads best), `center` takes a single pixel (faster on large videos, aliases)
+    #[arg(long, value_enum, default_value_t = Sample::Area)]
+    sample: Sample,
+    /// Export a contact sheet of COLSxROWS evenly spaced frames instead of every frame (.txt)
+    #[arg(long, value_name = "COLSxROWS", value_parser = parse_resolution)]
+    grid: Option<(u32, u32)>,
+    /// Font size in pixels used when rasterizing frames (e.g. screenshots with `s`)
+    #[cfg(feature = "render")]
+    #[arg(long, default_value_t = 12.0)]
+    font_size: f32,
+    /// Play in the normal screen buffer (no alternate screen) and leave the last frame in the
+    /// scrollback on exit
+    #[arg(long, visible_alias = "no-alt-screen", default_value_t = false)]
+    inline: bool,
+    /// Drag with the mouse or use the arrow keys to pan over output larger than the terminal,
+    /// scroll to zoom (captures the mouse, so text can't be selected while playing)
+    #[arg(long, default_value_t = false, requires = "new_lines")]
+    pannable: bool,
+    /// Fit the output to the terminal width and let tall images overflow, scrolled with the
+    /// arrow and page keys (meant for still images like screenshots or comics)
+    #[arg(long, default_value_t = false, requires = "new_lines")]
+    scroll: bool,
+    /// Show an overlay with the render fps, skipped frames and output resolution
+    #[arg(long, default_value_t = false)]
+    stats: bool,
+    /// Stop after this many frames (disables --loop)
+    #[arg(long)]
+    frames: Option<usize>,
+    /// Stretch the luminance levels (2nd to 98th percentile) to the full range, computed on the
+    /// first frame
+    #[arg(long, visible_alias = "normalize", default_value_t = false)]
+    auto_levels: bool,
+    /// Recompute the levels on every frame instead of only the first (may flicker)
+    #[arg(long, default_value_t = false, requires = "auto_levels")]
+    auto_levels_per_frame: bool,
+    /// Recompute the levels on every frame, averaged over the last FRAMES frames (no flicker)
+    #[arg(
+        long,
+        value_name = "FRAMES",
+        requires = "auto_levels",
+        conflicts_with = "auto_levels_per_frame"
+    )]
+    auto_levels_window: Option<u32>,
+    /// Equalize the luma histogram so the full char map is used (computed on the first frame)
+    #[arg(long, default_value_t = false)]
+    equalize: bool,
+    /// Recompute the equalization on every frame instead of only the first (may flicker)
+    #[arg(long, default_value_t = false, requires = "equalize")]
+    equalize_rolling: bool,
+    /// Map the luminance through a curve of IN:OUT control points (0-255), linearly interpolated,
+    /// e.g. "0:0,64:40,192:215,255:255" for an S-curve. Applied after the levels and equalization
+    #[arg(long, value_parser = parse_tone_curve)]
+    tone_curve: Option<ToneCurve>,
+    /// Draw chars from the char map, or fill every cell with its color (`blocks`)
+    #[arg(long, value_enum, default_value_t = Mode::Chars)]
+    mode: Mode,
+    /// Only convert this region of the source, in pixels (x,y,w,h), clamped to the image
+    #[arg(long, value_parser = parse_crop)]
+    crop: Option<Crop>,
+    /// Port to listen on for `serve`
+    #[cfg(feature = "server")]
+    #[arg(long, default_value_t = 8080)]
+    port: u16,
+    /// Color encoding, `256` for terminals without truecolor support, `16` for the standard
+    /// colors only (old terminals, CI logs)
+    #[arg(long, visible_alias = "color", value_enum, default_value_t = Depth::TrueColor)]
+    color_depth: Depth,
+    /// Dither colors when quantizing them to the 256 or 16-color palette (with --color-depth 256
+    /// or 16)
+    #[arg(long, default_value_t = false)]
+    color_dither: bool,
+    /// Snap colors to a palette: cga, gameboy, pico8, gray4, a list like "#0f380f,#9bbc0f" or a
+    /// .hex file with one color per line
+    #[arg(long, value_parser = parse_palette)]
+    palette: Option<Palette>,
+    /// Dither colors when snapping them to the palette
+    #[arg(long, default_value_t = false, requires = "palette")]
+    palette_dither: bool,
+    /// Color the chars by their luminance through a colormap instead of the source colors
+    #[arg(long, value_enum, conflicts_with = "gray")]
+    colormap: Option<ColormapName>,
+    /// Push detailed (high variance) areas towards denser glyphs, 0 disables (try 0.5)
+    #[arg(long, default_value_t = 0.0)]
+    detail: f32,
+    /// Draw transparent pixels of images as CHAR (a space by default) instead of compositing
+    /// them over --bg-color, e.g. for sprites
+    #[arg(long, value_name = "CHAR", num_args = 0..=1, default_missing_value = " ")]
+    transparent_char: Option<char>,
+    /// Alpha below which a cell counts as transparent (with --transparent-char)
+    #[arg(long, default_value_t = 128, requires = "transparent_char")]
+    alpha_threshold: u8,
+    /// Background color (#rrggbb) transparent images are composited over, also used for the
+    /// terminal background during playback
+    #[arg(long, value_parser = parse_color, default_value = "#000000")]
+    bg_color: [u8; 3],
+    /// Composite transparent images over a gray checkerboard instead of --bg-color, to show
+    /// which parts are transparent
+    #[arg(long, default_value_t = false)]
+    checker: bool,
+    /// Also color the cell backgrounds, with the cell color darkened by FACTOR (0-1, e.g.
+    /// 0.3), so glyphs stay legible in dark scenes
+    #[arg(long, value_name = "FACTOR", value_parser = parse_shade)]
+    bg_shade: Option<f32>,
+    /// Only change the char of a cell once its luminance moved more than N (0-255) from the
+    /// luminance of its last char, calms the shimmer of noisy video in flat areas
+    #[arg(long, value_name = "N")]
+    stabilize: Option<u8>,
+    /// Keep the transparency of the source in exported PNGs instead of drawing over
+    /// --bg-color, to composite the art over other content
+    #[arg(long, default_value_t = false)]
+    alpha: bool,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
@@ -48,69 +249,935 @@ struct Args {
 enum Action {
     Export,
     Play,
+    /// Print the detected media metadata and exit
+    Info,
+    /// Run the frames through the pipeline without drawing and report the time spent decoding,
+    /// resizing and converting them
+    Bench,
+    /// Stream the frames to TCP clients
+    #[cfg(feature = "server")]
+    Serve,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+#[clap(rename_all = "lower")]
+enum Mode {
+    Chars,
+    Blocks,
+    /// Match glyph shapes, export only
+    #[cfg(feature = "render")]
+    Structural,
+    /// Show the actual frames with the Kitty graphics protocol or iTerm2 inline images, play only
+    Kitty,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+#[clap(rename_all = "lower")]
+enum Sample {
+    Area,
+    Center,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+#[clap(rename_all = "lower")]
+enum ColormapName {
+    Viridis,
+    Magma,
+    Inferno,
+    Plasma,
+}
+
+impl From<ColormapName> for Colormap {
+    fn from(name: ColormapName) -> Self {
+        match name {
+            ColormapName::Viridis => Colormap::Viridis,
+            ColormapName::Magma => Colormap::Magma,
+            ColormapName::Inferno => Colormap::Inferno,
+            ColormapName::Plasma => Colormap::Plasma,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+#[clap(rename_all = "lower")]
+enum Depth {
+    TrueColor,
+    #[value(name = "256")]
+    Ansi256,
+    #[value(name = "16")]
+    Ansi16,
+}
+
+impl From<Depth> for ColorDepth {
+    fn from(depth: Depth) -> Self {
+        match depth {
+            Depth::TrueColor => ColorDepth::TrueColor,
+            Depth::Ansi256 => ColorDepth::Ansi256,
+            Depth::Ansi16 => ColorDepth::Ansi16,
+        }
+    }
 }
 
 const DEFAULT_FPS: f64 = 30.0;
+const DEFAULT_EXPORT_RESOLUTION: (u32, u32) = (80, 24);
+/// Height of a terminal cell relative to its width.
+const CELL_ASPECT: f64 = 2.0;
 
-use std::path::Path;
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{BufWriter, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, Instant},
+};
 
 use crate::terminal_player::TerminalPlayer;
 
-fn main() -> Result<(), Error> {
+fn main() -> ExitCode {
     let args = Args::parse();
+    init_logging(&args);
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Error::MissingDependency { tool, hint }) => {
+            eprintln!("iv2cli needs `{tool}`, but it could not be found.");
+            eprintln!("Hint: {hint}.");
+            ExitCode::FAILURE
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Logs to stderr at a level picked by `--quiet` / `--verbose`, warnings by default.
+fn init_logging(args: &Args) {
+    let level = match (args.quiet, args.verbose) {
+        (true, _) => tracing::Level::ERROR,
+        (false, 0) => tracing::Level::WARN,
+        (false, 1) => tracing::Level::INFO,
+        (false, 2) => tracing::Level::DEBUG,
+        (false, _) => tracing::Level::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .init();
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    if let (Some(start), Some(end)) = (args.start, args.end)
+        && end <= start
+    {
+        return Err(Error::Application(
+            "--end must be after --start".to_string(),
+        ));
+    }
+
+    if args.dump_chars {
+        return dump_chars(&args);
+    }
+    // clap requires both unless --dump-chars is given
+    let (Some(action), Some(path)) = (args.action, args.inputs.first().cloned()) else {
+        unreachable!("action and input are required");
+    };
+
+    if args.inputs.len() > 1 || args.out_dir.is_some() {
+        if action != Action::Export {
+            return Err(Error::Application(
+                "Several inputs and --out-dir only work with export".to_string(),
+            ));
+        }
+        return export_batch(args);
+    }
+    run_input(args, action, &path)
+}
+
+/// Opens the media at `path` and runs `action` on it.
+fn run_input(mut args: Args, action: Action, path: &str) -> Result<(), Error> {
+    let mut media_data = open_media(
+        Path::new(&path),
+        &OpenOptions {
+            video_stream: args.stream,
+            max_decode_bytes: args.max_decode_mem,
+            truncate_over_budget: args.truncate_decode,
+            video_read_retries: args.read_retries,
+            detect_fps: args.detect_fps,
+        },
+    )?;
+
+    if let (Some(seek), Some(duration)) = (args.seek, media_data.duration())
+        && seek > duration
+    {
+        return Err(Error::Application(format!(
+            "--seek {:.3}s is past the end of the media ({:.3}s)",
+            seek.as_secs_f64(),
+            duration.as_secs_f64()
+        )));
+    }
 
-    let path = args.input.clone();
+    let repeat = args.repeat.map(|frames| frames as usize).or_else(|| {
+        let fps = resolve_fps(&args, media_data.fps);
+        args.repeat_duration
+            .map(|duration| ((duration.as_secs_f64() * fps).ceil() as usize).max(1))
+    });
+    if let Some(frames) = repeat
+        && !media_data.repeat_still(frames)
+    {
+        tracing::warn!("--repeat only applies to still images, ignoring it");
+    }
+
+    if args.width.is_some() || args.height.is_some() {
+        args.output_resolution = Some(pinned_size(&args, &media_data)?);
+    }
+
+    #[cfg(feature = "render")]
+    if args.mode == Mode::Structural
+        && !matches!(action, Action::Export | Action::Info | Action::Bench)
+    {
+        return Err(Error::Application(
+            "--mode structural is too slow for playback, use it with export".to_string(),
+        ));
+    }
 
-    let media_data = open_media_from_path(Path::new(&path))?;
+    if args.mode == Mode::Kitty && !matches!(action, Action::Play | Action::Info) {
+        return Err(Error::Application(
+            "--mode kitty only works with play".to_string(),
+        ));
+    }
 
-    match args.action {
+    match action {
+        Action::Play if args.mode == Mode::Kitty => graphics::play(args, media_data),
         Action::Export => export(args, media_data),
         Action::Play => play(args, media_data),
+        Action::Info => info(&args, media_data),
+        Action::Bench => bench(&args, media_data),
+        #[cfg(feature = "server")]
+        Action::Serve => {
+            let port = args.port;
+            server::serve(args, media_data, port)
+        }
     }
 }
 
-fn export(_args: Args, _media_data: MediaData) -> Result<(), Error> {
+/// Exports every input into `--out-dir` in parallel, sharing the other options. Failures are
+/// collected instead of stopping the batch and reported in a summary at the end.
+fn export_batch(args: Args) -> Result<(), Error> {
+    let out_dir = args.out_dir.clone().ok_or(Error::Application(
+        "Exporting several inputs requires --out-dir".to_string(),
+    ))?;
+    std::fs::create_dir_all(&out_dir).map_err(|err| {
+        Error::Application(format!("Failed to create {}: {err}", out_dir.display()))
+    })?;
+
+    let results: Vec<(&String, Result<PathBuf, Error>)> = args
+        .inputs
+        .par_iter()
+        .map(|input| {
+            let stem = Path::new(input)
+                .file_stem()
+                .map_or_else(|| input.into(), OsStr::to_os_string);
+            let mut output = out_dir.join(stem);
+            if args.out_ext == "png" {
+                // a trailing separator makes export write the frames into a directory
+                output.push("");
+            } else {
+                output.set_extension(&args.out_ext);
+            }
+            let mut file_args = args.clone();
+            file_args.inputs = vec![input.clone()];
+            file_args.output = Some(output.to_string_lossy().into_owned());
+            file_args.out_dir = None;
+            let result = run_input(file_args, Action::Export, input);
+            (input, result.map(|()| output))
+        })
+        .collect();
+
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    for (input, result) in &results {
+        match result {
+            Ok(output) => println!("ok      {input} -> {}", output.display()),
+            Err(err) => println!("failed  {input}: {err}"),
+        }
+    }
+    println!("{} of {} exported", results.len() - failed, results.len());
+    if failed > 0 {
+        return Err(Error::Application(format!(
+            "{failed} of {} inputs failed to export",
+            results.len()
+        )));
+    }
     Ok(())
 }
 
+fn export(args: Args, media_data: MediaData) -> Result<(), Error> {
+    let output = args
+        .output
+        .as_deref()
+        .map(PathBuf::from)
+        .ok_or(Error::Application(
+            "Exporting requires an --output file".to_string(),
+        ))?;
+
+    let is_dir = output.is_dir()
+        || args
+            .output
+            .as_deref()
+            .is_some_and(|path| path.ends_with(std::path::is_separator));
+    if is_dir {
+        return export_images(&args, media_data, &output);
+    }
+
+    match output.extension().and_then(OsStr::to_str) {
+        Some("txt") if args.grid.is_some() => export_grid(&args, media_data, &output),
+        Some("txt") => export_text(&args, media_data, &output),
+        Some("ndjson") | Some("jsonl") => export_json(&args, media_data, &output),
+        _ => Err(Error::Application(format!(
+            "Unsupported export format: {}",
+            output.display()
+        ))),
+    }
+}
+
+/// Prints the format, dimensions, fps (and where it came from), frame count and duration.
+fn info(args: &Args, mut media_data: MediaData) -> Result<(), Error> {
+    let format = Path::new(args.inputs.first().map_or("", String::as_str))
+        .extension()
+        .and_then(OsStr::to_str)
+        .unwrap_or_default()
+        .to_lowercase();
+    let fps_source = match media_data.fps_source {
+        FpsSource::None => "none",
+        FpsSource::Ffprobe => "ffprobe",
+        FpsSource::OpenCv => "opencv",
+        FpsSource::FrameDelays => "frame delays",
+        FpsSource::Sampled => "sampled",
+    };
+    let frame_count = media_data.frame_count();
+    let duration = media_data.duration();
+    let dimensions = media_data
+        .frame_iter
+        .next()
+        .map(|frame| (frame.width(), frame.height()));
+
+    if args.json {
+        let json = serde_json::json!({
+            "format": format,
+            "width": dimensions.map(|(w, _)| w),
+            "height": dimensions.map(|(_, h)| h),
+            "fps": media_data.fps,
+            "fps_source": fps_source,
+            "frames": frame_count,
+            "duration": duration.map(|d| d.as_secs_f64()),
+        });
+        println!("{json}");
+        return Ok(());
+    }
+
+    let unknown = || "unknown".to_string();
+    println!("format:     {format}");
+    println!(
+        "dimensions: {}",
+        dimensions.map_or_else(unknown, |(w, h)| format!("{w}x{h}"))
+    );
+    println!(
+        "fps:        {} ({fps_source})",
+        media_data
+            .fps
+            .map_or_else(unknown, |fps| format!("{fps:.3}"))
+    );
+    println!(
+        "frames:     {}",
+        frame_count.map_or_else(unknown, |count| count.to_string())
+    );
+    println!(
+        "duration:   {}",
+        duration.map_or_else(unknown, |d| format!("{:.3}s", d.as_secs_f64()))
+    );
+    Ok(())
+}
+
+/// Renders the frames as fast as possible without drawing them and prints the throughput and
+/// the time spent in each stage, for comparing performance changes.
+fn bench(args: &Args, media_data: MediaData) -> Result<(), Error> {
+    let fps = resolve_fps(args, media_data.fps);
+    let pipeline = build_pipeline(args, export_resolution(args), args.new_lines)?;
+    let renderer = Renderer::new(
+        pipeline,
+        media_data.frame_iter,
+        RenderOptions {
+            fps,
+            w_mod: args.w_mod,
+            loop_playback: false,
+            start: args.start,
+            end: args.end,
+            max_frames: args.frames,
+            frame_delays: false,
+            frame_skip_mode: FrameSkipMode::Drop,
+            fps_cap: None,
+        },
+    );
+
+    let started = Instant::now();
+    let mut frames = renderer.frames();
+    let mut rendered = 0;
+    for frame in frames.by_ref() {
+        frame?;
+        rendered += 1;
+    }
+    let elapsed = started.elapsed();
+    if rendered == 0 {
+        return Err(Error::Application(ERROR_DATA.to_string()));
+    }
+
+    let stats = frames.stats();
+    let (width, height) = stats.resolution;
+    let stage = |name: &str, time: Duration| {
+        println!(
+            "{name:<10}{:>10.1} ms total {:>8.3} ms/frame",
+            time.as_secs_f64() * 1000.0,
+            time.as_secs_f64() * 1000.0 / rendered as f64
+        );
+    };
+    println!("frames:   {rendered} at {width}x{height}");
+    println!(
+        "total:    {:.3}s, {:.1} fps",
+        elapsed.as_secs_f64(),
+        rendered as f64 / elapsed.as_secs_f64()
+    );
+    stage("decode:", stats.decode_time);
+    stage("resize:", stats.resize_time);
+    stage("convert:", stats.convert_time);
+    Ok(())
+}
+
+/// Writes the plain characters of every frame to `output`, one line per row
+/// and frames separated by an empty line.
+fn export_text(args: &Args, media_data: MediaData, output: &Path) -> Result<(), Error> {
+    let fps = resolve_fps(args, media_data.fps);
+    let mut media = media_data.frame_iter;
+    let mut pipeline = build_pipeline(args, export_resolution(args), true)?;
+    if let Some(start) = args.start {
+        media.seek(start, fps);
+    }
+
+    let mut frames = Vec::new();
+    for frame in media.take(export_frame_limit(args, fps)) {
+        frames.push(text_rows(&mut pipeline, &frame)?.join("\n"));
+    }
+    if frames.is_empty() {
+        return Err(Error::Application(ERROR_DATA.to_string()));
+    }
+
+    let mut content = frames.join("\n\n");
+    content.push('\n');
+    std::fs::write(output, content)?;
+    Ok(())
+}
+
+/// Writes newline-delimited JSON, one object per frame with its characters (without line
+/// breaks) and one `[r, g, b]` color per character.
+fn export_json(args: &Args, media_data: MediaData, output: &Path) -> Result<(), Error> {
+    let fps = resolve_fps(args, media_data.fps);
+    let mut media = media_data.frame_iter;
+    let mut pipeline = build_pipeline(args, export_resolution(args), true)?;
+    if let Some(start) = args.start {
+        media.seek(start, fps);
+    }
+
+    let mut out = BufWriter::new(File::create(output)?);
+    for (index, frame) in media.take(export_frame_limit(args, fps)).enumerate() {
+        let render_frame = RenderFrame::from_image(&mut pipeline, &frame)?;
+        let lines = &render_frame.lines;
+        let json = serde_json::json!({
+            "index": index,
+            "width": lines.first().map_or(0, |line| line.chars().count()),
+            "height": lines.len(),
+            "chars": lines.concat(),
+            "colors": render_frame.colors,
+        });
+        writeln!(out, "{json}")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes every frame as a numbered PNG (`frame_00001.png`, ...) rendered with `--font-size`
+/// into the directory `output`, which is created if missing.
+#[cfg(feature = "render")]
+fn export_images(args: &Args, media_data: MediaData, output: &Path) -> Result<(), Error> {
+    let fps = resolve_fps(args, media_data.fps);
+    let limit = export_frame_limit(args, fps);
+    // pad to the number of frames when known, so the files sort in order
+    let digits = media_data
+        .frame_count()
+        .map_or(5, |count| count.min(limit as u64).to_string().len());
+    let mut media = media_data.frame_iter;
+    let mut pipeline = build_pipeline(args, export_resolution(args), true)?;
+    if let Some(start) = args.start {
+        media.seek(start, fps);
+    }
+
+    std::fs::create_dir_all(output).map_err(|err| {
+        Error::Application(format!("Failed to create {}: {err}", output.display()))
+    })?;
+    let [r, g, b] = args.bg_color;
+    for (index, frame) in media.take(limit).enumerate() {
+        let render_frame = RenderFrame::from_image(&mut pipeline, &frame)?;
+        let path = output.join(format!("frame_{:0digits$}.png", index + 1));
+        render_frame
+            .render_to_image(args.font_size, &[r, g, b, 255])
+            .save(&path)
+            .map_err(|err| {
+                Error::Application(format!("Failed to write {}: {err}", path.display()))
+            })?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "render"))]
+fn export_images(_args: &Args, _media_data: MediaData, _output: &Path) -> Result<(), Error> {
+    Err(Error::Application(
+        "Exporting images requires the `render` feature".to_string(),
+    ))
+}
+
+/// Writes a contact sheet of `--grid` frames, evenly spaced between `--start` and `--end`, as
+/// tiles that together fill the export resolution.
+fn export_grid(args: &Args, media_data: MediaData, output: &Path) -> Result<(), Error> {
+    let (cols, rows) = args.grid.unwrap_or((1, 1));
+    let fps = resolve_fps(args, media_data.fps);
+    let frame_count = media_data.frame_count().ok_or(Error::Application(
+        "--grid needs the frame count, which this video doesn't report".to_string(),
+    ))?;
+    let first = args
+        .start
+        .map_or(0, |start| (start.as_secs_f64() * fps) as u64);
+    let last = args.end.map_or(frame_count, |end| {
+        ((end.as_secs_f64() * fps) as u64).min(frame_count)
+    });
+    let span = last.saturating_sub(first).max(1);
+
+    let (width, height) = export_size(args);
+    // one column and row between tiles
+    let tile_width = (width.saturating_sub(cols - 1) / cols).max(1);
+    let tile_height = (height.saturating_sub(rows - 1) / rows).max(1);
+    let mut pipeline = build_pipeline(args, Resolution::Fixed(tile_width, tile_height), true)?;
+    // the tiles are far apart, holding cells from the previous one would mix them
+    pipeline.set_stabilize(None);
+
+    let mut media = media_data.frame_iter;
+    let mut tiles = Vec::new();
+    for i in 0..(cols * rows) as u64 {
+        let index = first + i * span / (cols * rows) as u64;
+        media.seek(
+            Duration::try_from_secs_f64(index as f64 / fps).unwrap_or_default(),
+            fps,
+        );
+        let Some(frame) = media.next() else {
+            break;
+        };
+        tiles.push(text_rows(&mut pipeline, &frame)?);
+    }
+    if tiles.is_empty() {
+        return Err(Error::Application(ERROR_DATA.to_string()));
+    }
+
+    let tile_columns = tiles
+        .iter()
+        .flatten()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut content = String::new();
+    for tile_row in tiles.chunks(cols as usize) {
+        let lines = tile_row.iter().map(Vec::len).max().unwrap_or(0);
+        for line in 0..lines {
+            let row: Vec<String> = tile_row
+                .iter()
+                .map(|tile| {
+                    let text = tile.get(line).map_or("", String::as_str);
+                    format!("{text:<tile_columns$}")
+                })
+                .collect();
+            content.push_str(row.join(" ").trim_end());
+            content.push('\n');
+        }
+        content.push('\n');
+    }
+    std::fs::write(output, content.trim_end_matches('\n').to_string() + "\n")?;
+    Ok(())
+}
+
+/// The rows of plain characters `pipeline` renders `frame` to.
+fn text_rows(pipeline: &mut ImagePipeline, frame: &DynamicImage) -> Result<Vec<String>, Error> {
+    RenderFrame::from_image(pipeline, frame).map(|render_frame| render_frame.lines)
+}
+
+/// Number of frames to export, honoring `--frames` and the `--start`/`--end` range.
+fn export_frame_limit(args: &Args, fps: f64) -> usize {
+    let range_limit = args.end.map(|end| {
+        let range = end.saturating_sub(args.start.unwrap_or_default());
+        (range.as_secs_f64() * fps).ceil() as usize
+    });
+
+    match (args.frames, range_limit) {
+        (Some(frames), Some(range)) => frames.min(range),
+        (Some(limit), None) | (None, Some(limit)) => limit,
+        (None, None) => usize::MAX,
+    }
+}
+
+/// The fps to play at, `--fps` overrides the fps detected from the media.
+fn resolve_fps(args: &Args, media_fps: Option<f64>) -> f64 {
+    args.fps.or(media_fps).unwrap_or(DEFAULT_FPS)
+}
+
+/// Resolution used when exporting, `--output-resolution` or else the terminal size if available.
+fn export_resolution(args: &Args) -> Resolution {
+    let (width, height) = export_size(args);
+    Resolution::Fixed(width, height)
+}
+
+/// The grid size pinned by `--width` / `--height`, deriving the missing dimension from the
+/// aspect ratio of the media and the cells.
+fn pinned_size(args: &Args, media_data: &MediaData) -> Result<(u32, u32), Error> {
+    if let (Some(width), Some(height)) = (args.width, args.height) {
+        return Ok((width, height));
+    }
+    let (media_width, media_height) = media_data.dimensions().ok_or(Error::Application(
+        "Could not detect the media size to derive the other dimension, pass both --width and \
+         --height"
+            .to_string(),
+    ))?;
+    // rows per column of the media, once cells are taken to be CELL_ASPECT times taller
+    let ratio = media_height as f64 / media_width as f64 / CELL_ASPECT;
+    Ok(match (args.width, args.height) {
+        (Some(width), _) => (width, ((width as f64 * ratio).round() as u32).max(1)),
+        (_, Some(height)) => (((height as f64 / ratio).round() as u32).max(1), height),
+        (None, None) => unreachable!("called with --width or --height"),
+    })
+}
+
+fn export_size(args: &Args) -> (u32, u32) {
+    args.output_resolution.unwrap_or_else(|| {
+        TerminalPlayer::size()
+            .map(|(w, h)| (w as u32, h as u32))
+            .unwrap_or(DEFAULT_EXPORT_RESOLUTION)
+    })
+}
+
+/// The char map given with `--char-map`, `CharMap::Dotted` by default.
+fn char_map(args: &Args) -> Result<CharMap, Error> {
+    args.char_map
+        .as_deref()
+        .map_or(Ok(CharMap::Dotted), CharMap::custom)
+}
+
+/// Prints the chars of the char map and the bands as parsed, one per line with their index,
+/// code point and display width.
+fn dump_chars(args: &Args) -> Result<(), Error> {
+    let print = |name: &str, chars: &[char]| {
+        println!("{name}: {} chars", chars.len());
+        for (i, c) in chars.iter().enumerate() {
+            println!(
+                "{i:>4}  {c:?}  U+{:04X}  width {}",
+                *c as u32,
+                display_width(*c)
+            );
+        }
+    };
+
+    let map = char_map(args)?;
+    print(map.name(), &map.chars());
+    for (range, chars) in &args.band {
+        let band = CharMap::custom(chars)?.chars();
+        print(&format!("band {}-{}", range.start(), range.end()), &band);
+    }
+    Ok(())
+}
+
+fn build_pipeline(
+    args: &Args,
+    resolution: Resolution,
+    new_lines: bool,
+) -> Result<ImagePipeline, Error> {
+    let mut builder = ImagePipeline::builder()
+        .resolution(resolution)
+        .char_map(char_map(args)?);
+    for (range, chars) in &args.band {
+        builder = builder.band(range.clone(), CharMap::custom(chars)?);
+    }
+
+    builder
+        .new_lines(new_lines)
+        .equalization(match (args.equalize, args.equalize_rolling) {
+            (true, true) => Equalization::Rolling,
+            (true, false) => Equalization::FirstFrame,
+            _ => Equalization::Off,
+        })
+        .auto_levels(
+            match (
+                args.auto_levels,
+                args.auto_levels_per_frame,
+                args.auto_levels_window,
+            ) {
+                (true, _, Some(frames)) => AutoLevels::Rolling(frames),
+                (true, true, None) => AutoLevels::PerFrame,
+                (true, false, None) => AutoLevels::FirstFrame,
+                _ => AutoLevels::Off,
+            },
+        )
+        .mode(match args.mode {
+            Mode::Chars => RenderMode::Chars,
+            Mode::Blocks => RenderMode::Blocks,
+            #[cfg(feature = "render")]
+            Mode::Structural => RenderMode::Structural,
+            // frames are shown as images without going through the pipeline
+            Mode::Kitty => RenderMode::Chars,
+        })
+        .crop(args.crop)
+        .autocrop(args.autocrop.map_or(AutoCrop::Off, AutoCrop::Frames))
+        .sample_point(match args.sample {
+            Sample::Area => SamplePoint::Area,
+            Sample::Center => SamplePoint::Center,
+        })
+        .detail(args.detail)
+        .palette(args.palette.clone())
+        .palette_dither(args.palette_dither)
+        .colormap(args.colormap.map(Colormap::from))
+        .alpha_threshold(args.transparent_char.map(|_| args.alpha_threshold))
+        .transparent_char(args.transparent_char.unwrap_or(' '))
+        .background(args.bg_color)
+        .checker(args.checker)
+        .tone_curve(args.tone_curve.clone())
+        .bg_shade(args.bg_shade)
+        .stabilize(args.stabilize)
+        .keep_alpha(args.alpha)
+        .build()
+}
+
 fn play(args: Args, media_data: MediaData) -> Result<(), Error> {
     let media = media_data.frame_iter;
-    let fps = media_data.fps;
+    let use_fps = resolve_fps(&args, media_data.fps);
 
-    let mut term = TerminalPlayer::new("Title".to_string(), args.gray);
+    let term = TerminalPlayer::new("Title".to_string(), args.gray)
+        .with_stats(args.stats)
+        .with_inline(args.inline)
+        .with_bg_color(args.bg_color)
+        .with_color_depth(args.color_depth.into())
+        .with_color_dither(args.color_dither)
+        .with_pannable(args.pannable)
+        .with_scroll(args.scroll)
+        .with_fixed_resolution(args.output_resolution.is_some());
+    #[cfg(feature = "render")]
+    let term = term.with_font_size(args.font_size);
+    let mut term = term;
 
+    term.install_signal_handler()?;
     term.init()?;
 
-    let (width, height) = TerminalPlayer::size().map(|(w, h)| (w as u32, h as u32))?;
+    let (width, height) = match args.output_resolution {
+        Some(resolution) => resolution,
+        None => TerminalPlayer::size().map(|(w, h)| (w as u32, h as u32))?,
+    };
 
-    let mut use_fps = DEFAULT_FPS;
-    if let Some(fps) = fps {
-        use_fps = fps;
-    }
-    if let Some(fps) = &args.fps {
-        use_fps = fps
-            .parse::<f64>()
-            .map_err(|err| Error::Application(format!("Data error: {err:?}")))?;
-    }
-    let cmaps = args
-        .char_map
-        .clone()
-        .map_or(CharMap::Dotted, |s| CharMap::custom(&s));
-    let w_mod = args.w_mod;
     let allow_frame_skip = args.allow_frame_skip;
     let new_lines = args.new_lines;
-    let loop_playback = args.r#loop;
 
-    let mut renderer = iv2c::render::Renderer::new(
-        ImagePipeline::new(Resolution::Fixed(width, height), cmaps, new_lines),
+    let resolution = if args.scroll {
+        Resolution::FitWidth(width)
+    } else {
+        Resolution::Fixed(width, height)
+    };
+    let mut pipeline = build_pipeline(&args, resolution, new_lines)?;
+    pipeline.set_grayscale(args.gray);
+
+    let mut renderer = build_renderer(&args, pipeline, media, use_fps);
+    renderer.run(allow_frame_skip, term.callback())?;
+    Ok(())
+}
+
+/// A renderer playing `media` in real time as configured by `args`, shared by playback and
+/// `serve`. Starts at `--seek` if given.
+fn build_renderer(
+    args: &Args,
+    pipeline: ImagePipeline,
+    media: FrameIterator,
+    fps: f64,
+) -> Renderer {
+    let mut renderer = Renderer::new(
+        pipeline,
         media,
         RenderOptions {
-            fps: use_fps,
-            w_mod,
-            loop_playback,
+            fps,
+            w_mod: args.w_mod,
+            loop_playback: args.r#loop && args.frames.is_none(),
+            start: args.start,
+            end: args.end,
+            max_frames: args.frames,
+            // a forced fps overrides the delays of animated images
+            frame_delays: args.fps.is_none(),
+            frame_skip_mode: if args.blend_skipped {
+                FrameSkipMode::Blend
+            } else {
+                FrameSkipMode::Drop
+            },
+            fps_cap: args.fps_cap,
         },
     );
+    if let Some(seek) = args.seek {
+        renderer.seek(seek);
+    }
+    renderer
+}
 
-    renderer.run(allow_frame_skip, term.callback())?;
-    Ok(())
+/// Parses a timestamp given as seconds, `mm:ss` or `hh:mm:ss` (seconds may be fractional).
+fn parse_timestamp(s: &str) -> Result<Duration, String> {
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() > 3 {
+        return Err(format!(
+            "Invalid timestamp `{s}`, expected seconds, mm:ss or hh:mm:ss"
+        ));
+    }
+
+    let mut seconds = 0_f64;
+    for part in parts {
+        let value = part
+            .trim()
+            .parse::<f64>()
+            .map_err(|err| format!("Invalid timestamp `{s}`: {err}"))?;
+        seconds = seconds * 60.0 + value;
+    }
+
+    Duration::try_from_secs_f64(seconds).map_err(|err| format!("Invalid timestamp `{s}`: {err}"))
+}
+
+/// Parses a `WxH` character grid size, both dimensions must be positive.
+fn parse_resolution(s: &str) -> Result<(u32, u32), String> {
+    let (width, height) = s.split_once(['x', 'X']).ok_or(format!(
+        "Invalid resolution `{s}`, expected WxH (e.g. 120x40)"
+    ))?;
+    let parse = |value: &str| match value.trim().parse::<u32>() {
+        Ok(0) => Err(format!(
+            "Invalid resolution `{s}`, dimensions must be positive"
+        )),
+        Ok(value) => Ok(value),
+        Err(err) => Err(format!("Invalid resolution `{s}`: {err}")),
+    };
+    Ok((parse(width)?, parse(height)?))
+}
+
+/// Parses a frame rate, which must be positive and finite.
+fn parse_fps(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(fps) if fps.is_finite() && fps > 0.0 => Ok(fps),
+        Ok(_) => Err(format!("Invalid fps `{s}`, must be a positive number")),
+        Err(err) => Err(format!("Invalid fps `{s}`: {err}")),
+    }
+}
+
+/// Parses a crop region given as `x,y,w,h` in source pixels.
+fn parse_crop(s: &str) -> Result<Crop, String> {
+    let values = s
+        .split(',')
+        .map(|value| value.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("Invalid crop `{s}`: {err}"))?;
+    let [x, y, width, height] = values[..] else {
+        return Err(format!("Invalid crop `{s}`, expected x,y,w,h"));
+    };
+    if width == 0 || height == 0 {
+        return Err(format!(
+            "Invalid crop `{s}`, width and height must be positive"
+        ));
+    }
+    Ok(Crop {
+        x,
+        y,
+        width,
+        height,
+    })
+}
+
+/// Parses a color given as `#rrggbb` (the `#` is optional).
+fn parse_color(s: &str) -> Result<[u8; 3], String> {
+    parse_hex(s).ok_or_else(|| format!("Invalid color `{s}`, expected #rrggbb"))
+}
+
+/// Parses a palette file, a built-in palette name or a list of `#rrggbb` colors.
+fn parse_palette(s: &str) -> Result<Palette, String> {
+    if Path::new(s).is_file() {
+        return Palette::from_file(s).map_err(|err| err.to_string());
+    }
+    Palette::parse(s).map_err(|err| err.to_string())
+}
+
+/// Parses a shading factor between 0 and 1.
+fn parse_shade(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(shade) if (0.0..=1.0).contains(&shade) => Ok(shade),
+        _ => Err(format!(
+            "Invalid shade `{s}`, expected a factor between 0 and 1"
+        )),
+    }
+}
+
+/// Parses a tone curve given as `IN:OUT` points, e.g. `0:0,128:200,255:255`.
+fn parse_tone_curve(s: &str) -> Result<ToneCurve, String> {
+    ToneCurve::parse(s).map_err(|err| err.to_string())
+}
+
+/// Parses a luminance band given as `LOW-HIGH:CHARS`, e.g. `200-255:#@`.
+fn parse_band(s: &str) -> Result<(RangeInclusive<u8>, String), String> {
+    let invalid = || format!("Invalid band `{s}`, expected LOW-HIGH:CHARS (e.g. 200-255:#@)");
+    let (range, chars) = s.split_once(':').ok_or_else(invalid)?;
+    let (low, high) = range.split_once('-').ok_or_else(invalid)?;
+    let parse = |value: &str| {
+        value
+            .trim()
+            .parse::<u8>()
+            .map_err(|err| format!("Invalid band `{s}`: {err}"))
+    };
+    let (low, high) = (parse(low)?, parse(high)?);
+    if low > high || chars.is_empty() {
+        return Err(invalid());
+    }
+    Ok((low..=high, chars.to_string()))
+}
+
+/// Parses a byte count with an optional binary `K`, `M` or `G` suffix, e.g. `512M`.
+fn parse_bytes(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let (number, unit) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 1 << 10),
+        Some((i, 'm' | 'M')) => (&s[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    number
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .ok_or_else(|| {
+            format!("Invalid size `{s}`, expected bytes with an optional K, M or G suffix")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fps_must_be_positive_and_finite() {
+        assert_eq!(parse_fps("23.976"), Ok(23.976));
+        assert_eq!(parse_fps(" 30 "), Ok(30.0));
+        for fps in ["0", "-5", "NaN", "inf", "-inf", "fast", ""] {
+            assert!(parse_fps(fps).is_err(), "accepted `{fps}`");
+        }
+    }
 }
@@ -0,0 +1,106 @@
+use std::{
+    io::Write,
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+use iv2c::{
+    color::ColorDepth,
+    error::Error,
+    frames::MediaData,
+    pipeline::Resolution,
+    render::{CallbackState, RenderFrame},
+};
+
+use crate::{Args, DEFAULT_EXPORT_RESOLUTION, build_pipeline, build_renderer, resolve_fps};
+
+/// Clear the screen, move home and hide the cursor, sent to every new client.
+const CLIENT_INIT: &[u8] = b"\x1b[2J\x1b[H\x1b[?25l";
+/// Clients that can't take a frame within this time are dropped instead of stalling the others.
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+/// Frames queued for a client before it counts as stalled and is dropped.
+const CLIENT_QUEUE_LEN: usize = 4;
+
+/// Streams the frames as ANSI text to every client connecting on `port` (e.g. `nc host 8080`).
+pub fn serve(args: Args, media_data: MediaData, port: u16) -> Result<(), Error> {
+    let fps = resolve_fps(&args, media_data.fps);
+    let (width, height) = args.output_resolution.unwrap_or(DEFAULT_EXPORT_RESOLUTION);
+    let pipeline = build_pipeline(&args, Resolution::Fixed(width, height), true)?;
+    let color_depth: ColorDepth = args.color_depth.into();
+
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    let clients = Arc::new(Mutex::new(Vec::new()));
+    {
+        let clients = Arc::clone(&clients);
+        thread::spawn(move || accept_clients(listener, clients));
+    }
+    eprintln!("Serving on port {port}, connect with `nc <host> {port}`");
+
+    let mut renderer = build_renderer(&args, pipeline, media_data.frame_iter, fps);
+    renderer.run(args.allow_frame_skip, |state: CallbackState| {
+        if state.should_render
+            && let Some(mut frame) = state.frame
+        {
+            if args.color_dither {
+                match color_depth {
+                    ColorDepth::Ansi256 => frame.dither_ansi256(),
+                    ColorDepth::Ansi16 => frame.dither_ansi16(),
+                    _ => {}
+                }
+            }
+            let payload: Arc<[u8]> = frame_payload(&frame, color_depth).into();
+            // clients whose writer thread ended (disconnected) or fell behind are dropped
+            if let Ok(mut clients) = clients.lock() {
+                clients.retain(|client: &Sender<Arc<[u8]>>| {
+                    client.try_send(Arc::clone(&payload)).is_ok()
+                });
+            }
+        }
+        thread::sleep(state.time_until_next_frame);
+        true
+    })
+}
+
+fn accept_clients(listener: TcpListener, clients: Arc<Mutex<Vec<Sender<Arc<[u8]>>>>>) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let _ = stream.set_nodelay(true);
+        let _ = stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT));
+        let (sender, receiver) = crossbeam_channel::bounded(CLIENT_QUEUE_LEN);
+        thread::spawn(move || write_frames(stream, receiver));
+        if let Ok(mut clients) = clients.lock() {
+            clients.push(sender);
+        }
+    }
+}
+
+/// Writes the queued frames to `stream` until the client disconnects, stalls past
+/// `CLIENT_WRITE_TIMEOUT` or is dropped from the client list.
+fn write_frames(mut stream: TcpStream, frames: Receiver<Arc<[u8]>>) {
+    if stream.write_all(CLIENT_INIT).is_err() {
+        return;
+    }
+    for payload in frames {
+        if stream.write_all(&payload).is_err() {
+            return;
+        }
+    }
+}
+
+/// The frame drawn from the top left, with `\r\n` so it also renders on clients whose terminal
+/// does not translate line feeds.
+fn frame_payload(frame: &RenderFrame, color_depth: ColorDepth) -> Vec<u8> {
+    let mut payload = String::from("\x1b[H");
+    for row in 0..frame.lines.len() {
+        if row > 0 {
+            payload.push_str("\r\n");
+        }
+        frame.push_ansi_row(&mut payload, row, .., color_depth);
+    }
+    payload.into_bytes()
+}
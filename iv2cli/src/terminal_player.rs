@@ -1,23 +1,93 @@
 use crate::RenderFrame;
 use crossterm::{
-    cursor::{Hide, MoveTo, Show},
-    event::{self, Event, KeyCode, KeyEvent},
+    cursor::{self, Hide, MoveTo, Show},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, MouseButton,
+        MouseEvent, MouseEventKind,
+    },
     execute,
-    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor, Stylize},
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
     terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, SetTitle},
 };
-use iv2c::{error::Error, pipeline::Resolution, render::CallbackState};
+use iv2c::{
+    color::ColorDepth,
+    error::Error,
+    maps::CharMap,
+    pipeline::Resolution,
+    render::{CallbackState, RenderStats},
+};
 use std::{
+    cell::{Cell, RefCell},
+    fmt::Write as _,
     io::{Result as IOResult, Write, stdout},
-    time::Duration,
+    panic::PanicHookInfo,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicU16, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
+/// Upper bound for blocking on input, so resizes are still picked up while idle.
+const MAX_POLL_TIMEOUT: Duration = Duration::from_millis(50);
+
+type PanicHook = dyn Fn(&PanicHookInfo<'_>) + Send + Sync;
+
+/// What `restore_terminal` needs to know about an initialized player.
+struct PanicRestore {
+    inline: bool,
+    mouse_capture: bool,
+    origin_row: Arc<AtomicU16>,
+    drawn_rows: Arc<AtomicU16>,
+}
+
+/// The initialized players, the panic hook restores the terminal for the most recent one. The
+/// hook is installed with the first player and the previous one put back after the last.
+static PANIC_RESTORE: Mutex<Vec<PanicRestore>> = Mutex::new(Vec::new());
+
+/// The panic hook that was installed before the first player replaced it.
+static PREVIOUS_PANIC_HOOK: Mutex<Option<Arc<PanicHook>>> = Mutex::new(None);
+
 #[derive(Debug)]
 pub struct TerminalPlayer {
     fg_color: Color,
     bg_color: Color,
     title: String,
     use_grayscale: bool,
+    /// Whether to draw the statistics overlay.
+    show_stats: bool,
+    /// Whether to play in the normal screen buffer instead of the alternate screen.
+    inline: bool,
+    /// Terminal row the frames are drawn from, below the existing output when playing inline.
+    /// Shared with the signal handler.
+    origin_row: Arc<AtomicU16>,
+    /// Number of terminal rows covered by the last drawn frame, shared with the signal handler.
+    drawn_rows: Arc<AtomicU16>,
+    /// Whether the output resolution is fixed instead of following terminal resizes.
+    fixed_resolution: bool,
+    /// Reused buffer for the escaped output of `draw`.
+    draw_buffer: RefCell<String>,
+    /// The frame currently on screen.
+    last_frame: RefCell<Option<RenderFrame>>,
+    /// When to restore the title after showing a temporary status in it.
+    title_reset_at: Cell<Option<Instant>>,
+    /// How colors are encoded in the output.
+    color_depth: ColorDepth,
+    /// Whether to dither colors when quantizing them to the 256-color palette.
+    color_dither: bool,
+    /// Index of the built-in char map shown, see `CharMap::built_ins`.
+    char_map_index: Cell<usize>,
+    /// Whether mouse drags pan over frames larger than the terminal.
+    pannable: bool,
+    /// Whether frames are fitted to the terminal width and scrolled vertically with the keys.
+    scroll: bool,
+    /// Column and row of the frame shown in the top left corner when panning or scrolling.
+    viewport: Cell<(usize, usize)>,
+    /// Last mouse position of an ongoing drag.
+    drag_position: Cell<Option<(u16, u16)>>,
+    /// Font size used for screenshots.
+    #[cfg(feature = "render")]
+    font_size: f32,
 }
 
 #[derive(PartialEq, Eq, Debug)]
@@ -25,8 +95,18 @@ enum Control {
     None,
     Exit,
     Resize(u16, u16),
+    Screenshot,
+    /// Switch to the next built-in char map.
+    NextCharMap,
+    /// Move the viewport by columns and rows.
+    Pan(isize, isize),
+    /// Scale the resolution up (`true`) or down.
+    Zoom(bool),
 }
 
+/// How long a status message stays in the terminal title.
+const TITLE_STATUS_DURATION: Duration = Duration::from_secs(2);
+
 impl TerminalPlayer {
     pub fn new(title: String, use_grayscale: bool) -> Self {
         Self {
@@ -34,16 +114,171 @@ impl TerminalPlayer {
             bg_color: Color::Black,
             title,
             use_grayscale,
+            show_stats: false,
+            inline: false,
+            origin_row: Arc::new(AtomicU16::new(0)),
+            drawn_rows: Arc::new(AtomicU16::new(0)),
+            fixed_resolution: false,
+            draw_buffer: RefCell::new(String::new()),
+            last_frame: RefCell::new(None),
+            title_reset_at: Cell::new(None),
+            color_depth: ColorDepth::TrueColor,
+            color_dither: false,
+            char_map_index: Cell::new(0),
+            pannable: false,
+            scroll: false,
+            viewport: Cell::new((0, 0)),
+            drag_position: Cell::new(None),
+            #[cfg(feature = "render")]
+            font_size: 12.0,
         }
     }
 
+    /// Enables the statistics overlay in the top left corner.
+    pub fn with_stats(mut self, show_stats: bool) -> Self {
+        self.show_stats = show_stats;
+        self
+    }
+
+    /// Plays in the normal screen buffer and leaves the last frame visible on exit.
+    pub fn with_inline(mut self, inline: bool) -> Self {
+        self.inline = inline;
+        self
+    }
+
+    /// Keeps the pipeline resolution when the terminal is resized.
+    pub fn with_fixed_resolution(mut self, fixed_resolution: bool) -> Self {
+        self.fixed_resolution = fixed_resolution;
+        self
+    }
+
+    /// Sets the terminal background color used during playback.
+    pub fn with_bg_color(mut self, [r, g, b]: [u8; 3]) -> Self {
+        self.bg_color = Color::Rgb { r, g, b };
+        self
+    }
+
+    /// Sets how colors are encoded, for terminals without truecolor support.
+    pub fn with_color_depth(mut self, color_depth: ColorDepth) -> Self {
+        self.color_depth = color_depth;
+        self
+    }
+
+    /// Dithers colors when they are quantized to the 256-color palette.
+    pub fn with_color_dither(mut self, color_dither: bool) -> Self {
+        self.color_dither = color_dither;
+        self
+    }
+
+    /// Captures the mouse so dragging pans over frames larger than the terminal and scrolling
+    /// zooms. Mouse capture prevents selecting text in the terminal.
+    pub fn with_pannable(mut self, pannable: bool) -> Self {
+        self.pannable = pannable;
+        self
+    }
+
+    /// Fits frames to the terminal width and lets taller ones overflow, scrolled with the arrow
+    /// and page keys. Meant for tall still images like screenshots or comics.
+    pub fn with_scroll(mut self, scroll: bool) -> Self {
+        self.scroll = scroll;
+        self
+    }
+
+    /// Sets the font size used to rasterize screenshots.
+    #[cfg(feature = "render")]
+    pub fn with_font_size(mut self, font_size: f32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
     pub fn init(&mut self) -> Result<(), Error> {
-        execute!(stdout(), EnterAlternateScreen, SetTitle(&self.title))?;
+        self.install_panic_hook();
+        if !self.inline {
+            execute!(stdout(), EnterAlternateScreen)?;
+        }
+        execute!(stdout(), SetTitle(&self.title))?;
+        if self.pannable {
+            execute!(stdout(), EnableMouseCapture)?;
+        }
         terminal::enable_raw_mode()?;
         self.clear()?;
         Ok(())
     }
 
+    /// Restores the terminal before a panic message is printed, which `Drop` can't do when the
+    /// panic aborts or happens on another thread. Nested players share one hook.
+    fn install_panic_hook(&self) {
+        let mut players = lock(&PANIC_RESTORE);
+        if players
+            .iter()
+            .any(|player| Arc::ptr_eq(&player.drawn_rows, &self.drawn_rows))
+        {
+            return;
+        }
+        if players.is_empty() {
+            let previous: Arc<PanicHook> = Arc::from(std::panic::take_hook());
+            *lock(&PREVIOUS_PANIC_HOOK) = Some(Arc::clone(&previous));
+            std::panic::set_hook(Box::new(move |info| {
+                // the panic may come from a thread holding the lock, don't wait for it
+                if let Ok(mut players) = PANIC_RESTORE.try_lock()
+                    && let Some(player) = players.last()
+                {
+                    let end_row = player
+                        .origin_row
+                        .load(Ordering::Relaxed)
+                        .saturating_add(player.drawn_rows.load(Ordering::Relaxed));
+                    let _ = restore_terminal(player.inline, player.mouse_capture, end_row);
+                    // restored once, `Drop` must not move the cursor over the message
+                    players.clear();
+                }
+                previous(info);
+            }));
+        }
+        players.push(PanicRestore {
+            inline: self.inline,
+            mouse_capture: self.pannable,
+            origin_row: Arc::clone(&self.origin_row),
+            drawn_rows: Arc::clone(&self.drawn_rows),
+        });
+    }
+
+    /// Unregisters the player from the panic hook, putting back the previous hook after the
+    /// last player. Returns whether the terminal still needs to be restored, `false` when the
+    /// player was never initialized or the panic hook restored it already.
+    fn release_panic_hook(&self) -> bool {
+        let mut players = lock(&PANIC_RESTORE);
+        let Some(index) = players
+            .iter()
+            .position(|player| Arc::ptr_eq(&player.drawn_rows, &self.drawn_rows))
+        else {
+            return false;
+        };
+        players.remove(index);
+        // the hook can't be changed while unwinding, it stays until the next player
+        if players.is_empty()
+            && !std::thread::panicking()
+            && let Some(previous) = lock(&PREVIOUS_PANIC_HOOK).take()
+        {
+            std::panic::set_hook(Box::new(move |info| previous(info)));
+        }
+        true
+    }
+
+    /// Restores the terminal when the process is interrupted or terminated by a signal, since
+    /// exiting from a signal skips `Drop`. In raw mode `Ctrl+C` arrives as a key event instead.
+    pub fn install_signal_handler(&self) -> Result<(), Error> {
+        let (inline, pannable) = (self.inline, self.pannable);
+        let (origin_row, drawn_rows) = (Arc::clone(&self.origin_row), Arc::clone(&self.drawn_rows));
+        ctrlc::set_handler(move || {
+            let end_row = origin_row
+                .load(Ordering::Relaxed)
+                .saturating_add(drawn_rows.load(Ordering::Relaxed));
+            let _ = restore_terminal(inline, pannable, end_row);
+            std::process::exit(130);
+        })
+        .map_err(|err| Error::Application(format!("Failed to install signal handler: {err}")))
+    }
+
     pub fn size() -> Result<(u16, u16), Error> {
         terminal::size().map_err(Into::into)
     }
@@ -53,17 +288,71 @@ impl TerminalPlayer {
              frame,
              should_render,
              pipeline,
+             time_until_next_frame,
+             stats,
          }| {
-            match self.poll_events() {
-                Control::Exit => return false,
-                Control::Resize(height, width) => {
-                    pipeline.set_resolution(Resolution::Fixed(height as u32, width as u32));
+            if should_render && let Some(mut f) = frame {
+                if self.color_dither {
+                    match self.color_depth {
+                        ColorDepth::Ansi256 => f.dither_ansi256(),
+                        ColorDepth::Ansi16 => f.dither_ansi16(),
+                        _ => {}
+                    }
+                }
+                let _ = self.draw(&f);
+                if self.show_stats {
+                    let _ = self.draw_stats(&stats);
                 }
-                Control::None => {}
+                *self.last_frame.borrow_mut() = Some(f);
             }
 
-            if should_render && let Some(f) = frame {
-                let _ = self.draw(&f);
+            if self
+                .title_reset_at
+                .get()
+                .is_some_and(|at| Instant::now() >= at)
+            {
+                self.title_reset_at.set(None);
+                let _ = execute!(stdout(), SetTitle(&self.title));
+            }
+
+            match self.poll_events(time_until_next_frame.min(MAX_POLL_TIMEOUT)) {
+                Control::Exit => return false,
+                Control::Resize(height, _) if self.scroll && !self.fixed_resolution => {
+                    pipeline.set_resolution(Resolution::FitWidth(height as u32));
+                }
+                Control::Resize(height, width) if !self.fixed_resolution => {
+                    pipeline.set_resolution(Resolution::Fixed(height as u32, width as u32));
+                }
+                Control::Screenshot => self.screenshot(),
+                Control::NextCharMap => {
+                    let char_maps = CharMap::built_ins();
+                    let index = (self.char_map_index.get() + 1) % char_maps.len();
+                    self.char_map_index.set(index);
+                    if let Some(char_map) = char_maps.into_iter().nth(index) {
+                        self.show_title_status(&format!("{} - {}", self.title, char_map.name()));
+                        pipeline.set_char_map(char_map);
+                    }
+                }
+                Control::Pan(columns, rows) => {
+                    let (x, y) = self.viewport.get();
+                    self.viewport.set((
+                        x.saturating_add_signed(columns),
+                        y.saturating_add_signed(rows),
+                    ));
+                    if let Some(frame) = &*self.last_frame.borrow() {
+                        let _ = self.draw(frame);
+                    }
+                }
+                Control::Zoom(zoom_in) => {
+                    if let Resolution::Fixed(width, height) = pipeline.resolution {
+                        let scale = |v: u32| {
+                            let scaled = if zoom_in { v + v / 4 } else { v - v / 5 };
+                            scaled.max(1)
+                        };
+                        pipeline.set_resolution(Resolution::Fixed(scale(width), scale(height)));
+                    }
+                }
+                Control::Resize(..) | Control::None => {}
             }
 
             true
@@ -71,6 +360,9 @@ impl TerminalPlayer {
     }
 
     fn clear(&self) -> IOResult<()> {
+        if self.inline {
+            return self.reserve_inline_rows();
+        }
         execute!(
             stdout(),
             Clear(ClearType::All),
@@ -83,21 +375,43 @@ impl TerminalPlayer {
         Ok(())
     }
 
-    fn cleanup(&self) -> IOResult<()> {
-        // Restore terminal state
+    /// Makes room for the frames below the existing output instead of clearing it, scrolling
+    /// it up into the scrollback as needed, and draws from there on.
+    fn reserve_inline_rows(&self) -> IOResult<()> {
+        let (_, rows) = terminal::size()?;
+        let newlines = "\n".repeat(rows.saturating_sub(1) as usize);
+        execute!(stdout(), Hide, Print(newlines))?;
+        let (_, bottom) = cursor::position()?;
+        self.origin_row.store(
+            bottom.saturating_sub(rows.saturating_sub(1)),
+            Ordering::Relaxed,
+        );
         execute!(
             stdout(),
-            ResetColor,
-            Clear(ClearType::All),
-            Show,
-            LeaveAlternateScreen
+            SetForegroundColor(self.fg_color),
+            SetBackgroundColor(self.bg_color),
+            MoveTo(0, self.origin()),
         )?;
-        terminal::disable_raw_mode()?;
+        stdout().flush()?;
         Ok(())
     }
 
-    fn poll_events(&self) -> Control {
-        if event::poll(Duration::from_millis(10)).is_ok_and(|r| r) {
+    /// Terminal row the frames are drawn from, see `origin_row`.
+    pub fn origin(&self) -> u16 {
+        self.origin_row.load(Ordering::Relaxed)
+    }
+
+    fn cleanup(&self) -> IOResult<()> {
+        restore_terminal(
+            self.inline,
+            self.pannable,
+            self.origin()
+                .saturating_add(self.drawn_rows.load(Ordering::Relaxed)),
+        )
+    }
+
+    fn poll_events(&self, timeout: Duration) -> Control {
+        if event::poll(timeout).is_ok_and(|r| r) {
             let Ok(ev) = event::read() else {
                 return Control::None;
             };
@@ -115,7 +429,19 @@ impl TerminalPlayer {
                 | Event::Key(KeyEvent {
                     code: KeyCode::Esc, ..
                 }) => Control::Exit,
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('s') | KeyCode::Char('S'),
+                    ..
+                }) => Control::Screenshot,
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('m') | KeyCode::Char('M'),
+                    ..
+                }) => Control::NextCharMap,
                 Event::Resize(width, height) => Control::Resize(width, height),
+                Event::Key(KeyEvent { code, .. }) if self.pannable || self.scroll => {
+                    self.key_control(code)
+                }
+                Event::Mouse(mouse) if self.pannable => self.mouse_control(mouse),
                 _ => Control::None,
             };
         }
@@ -123,33 +449,249 @@ impl TerminalPlayer {
         Control::None
     }
 
-    fn draw(&self, RenderFrame { text, colors }: &RenderFrame) -> IOResult<()> {
-        let print_string = |string: &str| {
-            let mut out = stdout();
-            execute!(out, MoveTo(0, 0), Print(string), MoveTo(0, 0))?;
-            out.flush()?;
-            Ok(())
-        };
+    /// Turns the arrow, page, `Home` and `End` keys into moving the viewport.
+    fn key_control(&self, code: KeyCode) -> Control {
+        let page = terminal::size().map_or(1, |(_, rows)| rows.saturating_sub(1).max(1)) as isize;
+        match code {
+            KeyCode::Up => Control::Pan(0, -1),
+            KeyCode::Down => Control::Pan(0, 1),
+            KeyCode::Left => Control::Pan(-1, 0),
+            KeyCode::Right => Control::Pan(1, 0),
+            KeyCode::PageUp => Control::Pan(0, -page),
+            KeyCode::PageDown => Control::Pan(0, page),
+            // the viewport is clamped to the frame when it is drawn
+            KeyCode::Home => Control::Pan(0, isize::MIN),
+            KeyCode::End => Control::Pan(0, isize::MAX),
+            _ => Control::None,
+        }
+    }
 
-        if self.use_grayscale {
-            print_string(text)
-        } else {
-            let mut colored_string = String::with_capacity(text.len() * 10);
-            for (c, rgb) in text.chars().zip(colors.chunks(3)) {
-                let color = Color::Rgb {
-                    r: rgb[0],
-                    g: rgb[1],
-                    b: rgb[2],
+    /// Tracks left button drags as pans (moving the frame along with the mouse) and turns
+    /// the scroll wheel into zooming.
+    fn mouse_control(&self, mouse: MouseEvent) -> Control {
+        let position = (mouse.column, mouse.row);
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.drag_position.set(Some(position));
+                Control::None
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                let Some((last_column, last_row)) = self.drag_position.replace(Some(position))
+                else {
+                    return Control::None;
                 };
-                colored_string.push_str(&format!("{}", c.stylize().with(color)));
+                Control::Pan(
+                    last_column as isize - mouse.column as isize,
+                    last_row as isize - mouse.row as isize,
+                )
+            }
+            MouseEventKind::Up(MouseButton::Left) => {
+                self.drag_position.set(None);
+                Control::None
             }
-            print_string(&colored_string)
+            MouseEventKind::ScrollUp => Control::Zoom(true),
+            MouseEventKind::ScrollDown => Control::Zoom(false),
+            _ => Control::None,
         }
     }
+
+    /// Draws the frame, positioning every line explicitly with `MoveTo` instead of relying on
+    /// embedded `\r\n`, which some consoles (notably on Windows) turn into doubled line breaks.
+    /// Without `--new-lines` the frame is a single line that is wrapped by the terminal.
+    fn draw(&self, frame: &RenderFrame) -> IOResult<()> {
+        let mut buffer = self.draw_buffer.borrow_mut();
+        buffer.clear();
+
+        let mut rows = 0;
+        // a single line (no `--new-lines`) is wrapped by the terminal
+        let (columns, term_rows) =
+            terminal::size().map_or((1, usize::MAX), |(w, h)| (w.max(1) as usize, h as usize));
+        let viewport = self.pannable || self.scroll;
+        let (pan_x, pan_y) = if viewport {
+            self.clamp_viewport(frame, columns, term_rows)
+        } else {
+            (0, 0)
+        };
+        let gray_frame;
+        let frame = if self.use_grayscale && frame.fill {
+            // blocks have no glyph to show, fill them with their luminance instead
+            gray_frame = luminance_frame(frame);
+            &gray_frame
+        } else {
+            frame
+        };
+        // grayscale glyphs keep the terminal's colors
+        let depth = if self.use_grayscale && !frame.fill {
+            ColorDepth::None
+        } else {
+            self.color_depth
+        };
+        let shown_columns = if self.pannable {
+            pan_x..pan_x + columns
+        } else {
+            0..usize::MAX
+        };
+        let visible_rows =
+            (pan_y..frame.lines.len()).take(if viewport { term_rows } else { usize::MAX });
+        for (row, frame_row) in visible_rows.enumerate() {
+            let shown = frame.lines[frame_row]
+                .chars()
+                .count()
+                .saturating_sub(shown_columns.start)
+                .min(shown_columns.len());
+            rows += shown.div_ceil(columns).max(1);
+            let _ = write!(
+                buffer,
+                "{}",
+                MoveTo(0, self.origin().saturating_add(row as u16))
+            );
+            frame.push_ansi_row(&mut buffer, frame_row, shown_columns.clone(), depth);
+        }
+
+        self.drawn_rows.store(rows as u16, Ordering::Relaxed);
+
+        let mut out = stdout();
+        execute!(out, Print(&*buffer), MoveTo(0, self.origin()))?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Keeps the viewport within `frame`, returns the clamped (column, row) offset.
+    fn clamp_viewport(&self, frame: &RenderFrame, columns: usize, rows: usize) -> (usize, usize) {
+        let frame_columns = frame
+            .lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        let (x, y) = self.viewport.get();
+        let clamped = (
+            x.min(frame_columns.saturating_sub(columns)),
+            y.min(frame.lines.len().saturating_sub(rows)),
+        );
+        self.viewport.set(clamped);
+        clamped
+    }
+
+    /// Saves the frame on screen as a timestamped PNG in the working directory.
+    #[cfg(feature = "render")]
+    fn screenshot(&self) {
+        let Some(frame) = &*self.last_frame.borrow() else {
+            return;
+        };
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let file_name = format!("iv2c_{timestamp}.png");
+        let status = match frame
+            .render_to_image(self.font_size, &[0, 0, 0, 255])
+            .save(&file_name)
+        {
+            Ok(()) => format!("{} - saved {file_name}", self.title),
+            Err(err) => format!("{} - screenshot failed: {err}", self.title),
+        };
+        self.show_title_status(&status);
+    }
+
+    #[cfg(not(feature = "render"))]
+    fn screenshot(&self) {
+        self.show_title_status(&format!(
+            "{} - screenshots require the `render` feature",
+            self.title
+        ));
+    }
+
+    /// Shows `status` in the terminal title for `TITLE_STATUS_DURATION`.
+    fn show_title_status(&self, status: &str) {
+        let _ = execute!(stdout(), SetTitle(status));
+        self.title_reset_at
+            .set(Some(Instant::now() + TITLE_STATUS_DURATION));
+    }
+
+    fn draw_stats(&self, stats: &RenderStats) -> IOResult<()> {
+        let (width, height) = stats.resolution;
+        let hud = format!(
+            " {:.1} fps | source {:.1} fps | skipped {} | {}x{} ",
+            stats.render_fps, stats.source_fps, stats.frames_skipped, width, height
+        );
+
+        let mut out = stdout();
+        execute!(
+            out,
+            MoveTo(0, self.origin()),
+            SetBackgroundColor(Color::DarkBlue),
+            SetForegroundColor(Color::White),
+            Print(hud),
+            SetForegroundColor(self.fg_color),
+            SetBackgroundColor(self.bg_color),
+            MoveTo(0, self.origin()),
+        )?;
+        out.flush()?;
+        Ok(())
+    }
 }
 
 impl Drop for TerminalPlayer {
     fn drop(&mut self) {
-        self.cleanup().expect("Failed to clean up Terminal.");
+        if !self.release_panic_hook() {
+            return;
+        }
+        // panicking in drop could abort, report the error instead
+        if let Err(err) = self.cleanup() {
+            tracing::error!(%err, "failed to clean up terminal");
+        }
     }
 }
+
+/// Locks `mutex` even if a panic poisoned it, the data stays consistent between statements.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// A copy of the filled `frame` with every color replaced by its luminance.
+fn luminance_frame(frame: &RenderFrame) -> RenderFrame {
+    let colors = frame
+        .colors
+        .iter()
+        .map(|rgb| {
+            let [r, g, b] = rgb.map(u32::from);
+            [((r * 299 + g * 587 + b * 114) / 1000) as u8; 3]
+        })
+        .collect();
+    RenderFrame {
+        lines: frame.lines.clone(),
+        colors,
+        fill: frame.fill,
+        bg_colors: Vec::new(),
+        alphas: Vec::new(),
+    }
+}
+
+/// Leaves raw mode and shows the cursor again. Inline playback keeps the last frame and continues
+/// at `end_row` below it, otherwise the alternate screen is left.
+fn restore_terminal(inline: bool, mouse_capture: bool, end_row: u16) -> IOResult<()> {
+    if mouse_capture {
+        execute!(stdout(), DisableMouseCapture)?;
+    }
+    if inline {
+        // keep the last frame, continue below it
+        execute!(stdout(), ResetColor, MoveTo(0, end_row), Show)?;
+        terminal::disable_raw_mode()?;
+        execute!(stdout(), Print("\n"))?;
+        return Ok(());
+    }
+
+    execute!(
+        stdout(),
+        ResetColor,
+        Clear(ClearType::All),
+        Show,
+        LeaveAlternateScreen
+    )?;
+    terminal::disable_raw_mode()?;
+    Ok(())
+}